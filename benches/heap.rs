@@ -0,0 +1,249 @@
+//! Compares insert and remove throughput of the timer heap against the binary
+//! heap it replaced, which is kept below as the baseline.
+//!
+//! Run with `cargo bench --bench heap`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use futures_timer::heap::Heap;
+
+const TIMERS: usize = 100_000;
+const ROUNDS: u32 = 10;
+
+/// The operations timed on each heap.
+trait Bench {
+    type Slot;
+
+    fn new() -> Self;
+    fn push(&mut self, key: u64) -> Self::Slot;
+    fn remove(&mut self, slot: Self::Slot) -> u64;
+}
+
+impl Bench for Heap<u64> {
+    type Slot = futures_timer::heap::Slot;
+
+    fn new() -> Self {
+        Heap::new()
+    }
+
+    fn push(&mut self, key: u64) -> Self::Slot {
+        Heap::push(self, key)
+    }
+
+    fn remove(&mut self, slot: Self::Slot) -> u64 {
+        Heap::remove(self, slot).unwrap()
+    }
+}
+
+impl Bench for binary::Heap<u64> {
+    type Slot = binary::Slot;
+
+    fn new() -> Self {
+        binary::Heap::new()
+    }
+
+    fn push(&mut self, key: u64) -> Self::Slot {
+        binary::Heap::push(self, key)
+    }
+
+    fn remove(&mut self, slot: Self::Slot) -> u64 {
+        binary::Heap::remove(self, slot)
+    }
+}
+
+/// A deterministic stream of pseudo-random numbers, so both heaps see the
+/// same keys and removal order.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        // xorshift64
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+fn bench<H: Bench>(name: &str) {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    let keys = (0..TIMERS).map(|_| rng.next()).collect::<Vec<_>>();
+    // Timers are mostly cancelled in no particular order, so shuffle the
+    // order they're removed in.
+    let mut order = (0..TIMERS).collect::<Vec<_>>();
+    for i in (1..TIMERS).rev() {
+        order.swap(i, rng.next() as usize % (i + 1));
+    }
+
+    let mut insert = Duration::from_secs(0);
+    let mut remove = Duration::from_secs(0);
+    for _ in 0..ROUNDS {
+        let mut heap = H::new();
+
+        let start = Instant::now();
+        let mut slots = keys
+            .iter()
+            .map(|&key| Some(heap.push(key)))
+            .collect::<Vec<_>>();
+        insert += start.elapsed();
+
+        let start = Instant::now();
+        for &i in &order {
+            black_box(heap.remove(slots[i].take().unwrap()));
+        }
+        remove += start.elapsed();
+    }
+    println!(
+        "{:<8} {} inserts: {:?}, {} removes: {:?}",
+        name,
+        TIMERS,
+        insert / ROUNDS,
+        TIMERS,
+        remove / ROUNDS,
+    );
+}
+
+fn main() {
+    bench::<binary::Heap<u64>>("binary");
+    bench::<Heap<u64>>("4-ary");
+}
+
+/// The binary heap the timer used before switching to a 4-ary heap, less its
+/// consistency checks.
+mod binary {
+    use std::mem;
+
+    pub struct Heap<T> {
+        // Binary heap of items, plus the slab index indicating what position in the
+        // list they're in.
+        items: Vec<(T, usize)>,
+
+        // A map from a slab index (assigned to an item above) to the actual index
+        // in the array the item appears at.
+        index: Vec<SlabSlot<usize>>,
+        next_index: usize,
+    }
+
+    enum SlabSlot<T> {
+        Empty { next: usize },
+        Full { value: T },
+    }
+
+    pub struct Slot {
+        idx: usize,
+    }
+
+    impl<T: Ord> Heap<T> {
+        pub fn new() -> Heap<T> {
+            Heap {
+                items: Vec::new(),
+                index: Vec::new(),
+                next_index: 0,
+            }
+        }
+
+        pub fn push(&mut self, t: T) -> Slot {
+            let len = self.items.len();
+            let slot = SlabSlot::Full { value: len };
+            let slot_idx = if self.next_index == self.index.len() {
+                self.next_index += 1;
+                self.index.push(slot);
+                self.index.len() - 1
+            } else {
+                match mem::replace(&mut self.index[self.next_index], slot) {
+                    SlabSlot::Empty { next } => mem::replace(&mut self.next_index, next),
+                    SlabSlot::Full { .. } => panic!(),
+                }
+            };
+            self.items.push((t, slot_idx));
+            self.percolate_up(len);
+            Slot { idx: slot_idx }
+        }
+
+        pub fn remove(&mut self, slot: Slot) -> T {
+            let empty = SlabSlot::Empty {
+                next: self.next_index,
+            };
+            let idx = match mem::replace(&mut self.index[slot.idx], empty) {
+                SlabSlot::Full { value } => value,
+                SlabSlot::Empty { .. } => panic!(),
+            };
+            self.next_index = slot.idx;
+            let (item, slot_idx) = self.items.swap_remove(idx);
+            debug_assert_eq!(slot.idx, slot_idx);
+            if idx < self.items.len() {
+                set_index(&mut self.index, self.items[idx].1, idx);
+                if self.items[idx].0 < item {
+                    self.percolate_up(idx);
+                } else {
+                    self.percolate_down(idx);
+                }
+            }
+            item
+        }
+
+        fn percolate_up(&mut self, mut idx: usize) -> usize {
+            while idx > 0 {
+                let parent = (idx - 1) / 2;
+                if self.items[idx].0 >= self.items[parent].0 {
+                    break;
+                }
+                let (a, b) = self.items.split_at_mut(idx);
+                mem::swap(&mut a[parent], &mut b[0]);
+                set_index(&mut self.index, a[parent].1, parent);
+                set_index(&mut self.index, b[0].1, idx);
+                idx = parent;
+            }
+            idx
+        }
+
+        fn percolate_down(&mut self, mut idx: usize) -> usize {
+            loop {
+                let left = 2 * idx + 1;
+                let right = 2 * idx + 2;
+
+                let mut swap_left = true;
+                match (self.items.get(left), self.items.get(right)) {
+                    (Some(left), None) => {
+                        if left.0 >= self.items[idx].0 {
+                            break;
+                        }
+                    }
+                    (Some(left), Some(right)) => {
+                        if left.0 < self.items[idx].0 {
+                            if right.0 < left.0 {
+                                swap_left = false;
+                            }
+                        } else if right.0 < self.items[idx].0 {
+                            swap_left = false;
+                        } else {
+                            break;
+                        }
+                    }
+
+                    (None, None) => break,
+                    (None, Some(_right)) => panic!("not possible"),
+                }
+
+                let (a, b) = if swap_left {
+                    self.items.split_at_mut(left)
+                } else {
+                    self.items.split_at_mut(right)
+                };
+                mem::swap(&mut a[idx], &mut b[0]);
+                set_index(&mut self.index, a[idx].1, idx);
+                set_index(&mut self.index, b[0].1, a.len());
+                idx = a.len();
+            }
+            idx
+        }
+    }
+
+    fn set_index<T>(slab: &mut [SlabSlot<T>], slab_slot: usize, val: T) {
+        match slab[slab_slot] {
+            SlabSlot::Full { ref mut value } => *value = val,
+            SlabSlot::Empty { .. } => panic!(),
+        }
+    }
+}
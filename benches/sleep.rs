@@ -0,0 +1,38 @@
+//! Compares `sleep` for a short duration with and without the spin path.
+//!
+//! Run with `cargo bench --bench sleep`.
+
+use std::time::{Duration, Instant};
+
+use futures_timer::{set_sleep_spin_threshold, sleep};
+
+const ITERS: u32 = 10_000;
+
+fn bench(name: &str, threshold: Duration, dur: Duration) {
+    set_sleep_spin_threshold(threshold);
+    async_std::task::block_on(async {
+        // Warm up, spawning the global timer thread if it isn't running.
+        sleep(dur).await;
+
+        let start = Instant::now();
+        for _ in 0..ITERS {
+            sleep(dur).await;
+        }
+        let per_iter = start.elapsed() / ITERS;
+        println!(
+            "{:<8} sleep({:?}): {:?}/iter, {:?} overshoot",
+            name,
+            dur,
+            per_iter,
+            per_iter.saturating_sub(dur),
+        );
+    });
+}
+
+fn main() {
+    for &micros in &[1, 10, 50] {
+        let dur = Duration::from_micros(micros);
+        bench("timer", Duration::from_secs(0), dur);
+        bench("spin", dur, dur);
+    }
+}
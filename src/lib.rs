@@ -16,12 +16,129 @@
 #![deny(missing_docs)]
 #![warn(missing_debug_implementations)]
 
+use std::convert::TryFrom;
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+use std::task::Poll;
+use std::time::Duration;
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm-bindgen"))]
+use instant::Instant;
+#[cfg(not(all(target_arch = "wasm32", feature = "wasm-bindgen")))]
+use std::time::Instant;
+
+/// Emits a `tracing` event at the trace level if the `tracing` feature is
+/// enabled, and compiles to nothing otherwise.
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::trace!($($arg)*);
+    };
+}
+
 #[cfg(not(all(target_arch = "wasm32", feature = "wasm-bindgen")))]
 mod native;
+mod timeout;
 #[cfg(all(target_arch = "wasm32", feature = "wasm-bindgen"))]
 mod wasm;
 
 #[cfg(not(all(target_arch = "wasm32", feature = "wasm-bindgen")))]
-pub use self::native::Delay;
+pub use self::native::heap;
+#[cfg(not(all(target_arch = "wasm32", feature = "wasm-bindgen")))]
+pub use self::native::{
+    global_idle, set_global_driver, shutdown_global_timer, ClockError, CoarseDelay, CoarseTimer,
+    Delay, DelayBuilder, DelayCanceller, DelayId, DelayQueue, DelaySet, Expired,
+    GlobalTimerBuilder, Key, ResetNotify, ShutdownGuard, SystemDelay, Ticker, Timer, TimerDriver,
+    TimerHandle, WeakDelay,
+};
+#[cfg(all(
+    feature = "metrics",
+    not(all(target_arch = "wasm32", feature = "wasm-bindgen"))
+))]
+pub use self::native::{Histogram, WakeupStats};
 #[cfg(all(target_arch = "wasm32", feature = "wasm-bindgen"))]
 pub use self::wasm::Delay;
+
+pub use self::timeout::{
+    elapsed_error, timeout, timeout_and_cancel, timeout_or_else, timeout_with, with_deadline,
+    Elapsed, Timeout, TimeoutGuard,
+};
+
+/// Creates a boxed `Delay` which fires after `dur`.
+///
+/// This is handy when timers need to be stored behind trait objects or in
+/// collections alongside other futures, without naming the `Delay` type. On
+/// wasm the returned future must be polled on the thread that created it.
+pub fn boxed_delay(dur: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+    Box::pin(Delay::new(dur))
+}
+
+/// Yields to the executor until the timer has had a chance to run.
+///
+/// A `Delay` for a zero duration, or for an instant which has already passed,
+/// resolves on its first poll without ever being handed to the timer. This
+/// instead always returns `Pending` at least once and is only woken once the
+/// timer has processed it, so the task is genuinely rescheduled behind the
+/// timer's next wakeup.
+pub async fn yield_now() {
+    #[cfg(not(all(target_arch = "wasm32", feature = "wasm-bindgen")))]
+    let mut delay = {
+        let mut delay = Delay::new_registered(Instant::now(), Default::default());
+        delay.wait_for_timer();
+        delay
+    };
+    #[cfg(all(target_arch = "wasm32", feature = "wasm-bindgen"))]
+    let mut delay = Delay::new(Duration::from_secs(0));
+
+    let mut yielded = false;
+    future::poll_fn(|cx| {
+        let ready = Pin::new(&mut delay).poll(cx);
+        if yielded {
+            return ready;
+        }
+        yielded = true;
+        if ready.is_ready() {
+            cx.waker().wake_by_ref();
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+/// Durations at or below this many nanoseconds are slept by `sleep` without a
+/// timer.
+static SLEEP_SPIN_THRESHOLD: AtomicU64 = AtomicU64::new(50_000);
+
+/// Sleeps for `dur`.
+///
+/// Longer sleeps are just a `Delay`. A sleep no longer than the threshold set
+/// with `set_sleep_spin_threshold`, 50µs by default, skips the timer
+/// entirely: the task wakes itself on every poll until the deadline has
+/// passed. That avoids allocating a timer node and the round trip through the
+/// timer thread, so it's cheaper and more precise for very short sleeps in
+/// hot paths, but the task keeps its executor thread busy for the whole
+/// duration instead of letting it park.
+pub async fn sleep(dur: Duration) {
+    if dur.as_nanos() > u128::from(SLEEP_SPIN_THRESHOLD.load(Relaxed)) {
+        return Delay::new(dur).await;
+    }
+    let deadline = Instant::now() + dur;
+    future::poll_fn(|cx| {
+        if Instant::now() >= deadline {
+            return Poll::Ready(());
+        }
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    })
+    .await
+}
+
+/// Sets the longest duration `sleep` busy-polls for instead of using a timer.
+///
+/// This affects every later call to `sleep` in the process. A threshold of
+/// zero makes every sleep go through a `Delay`.
+pub fn set_sleep_spin_threshold(d: Duration) {
+    let nanos = u64::try_from(d.as_nanos()).unwrap_or(u64::MAX);
+    SLEEP_SPIN_THRESHOLD.store(nanos, Relaxed);
+}
@@ -1,15 +1,37 @@
 mod arc_list;
 mod atomic_waker;
+mod coarse;
 mod delay;
+mod delay_queue;
+mod delay_set;
 mod global;
-mod heap;
+pub mod heap;
 mod heap_timer;
+#[cfg(feature = "metrics")]
+mod histogram;
+mod system_delay;
+mod ticker;
 mod timer;
 
 use self::arc_list::{ArcList, Node};
 use self::atomic_waker::AtomicWaker;
 use self::heap::{Heap, Slot};
 use self::heap_timer::HeapTimer;
-use self::timer::{ScheduledTimer, Timer, TimerHandle};
+use self::timer::ScheduledTimer;
 
-pub use self::delay::Delay;
+pub use self::coarse::{CoarseDelay, CoarseTimer};
+pub use self::delay::{
+    ClockError, Delay, DelayBuilder, DelayCanceller, DelayId, ResetNotify, WeakDelay,
+};
+pub use self::delay_queue::{DelayQueue, Expired, Key};
+pub use self::delay_set::DelaySet;
+pub use self::global::{
+    global_idle, set_global_driver, shutdown_global_timer, GlobalTimerBuilder, TimerDriver,
+};
+#[cfg(feature = "metrics")]
+pub use self::histogram::Histogram;
+pub use self::system_delay::SystemDelay;
+pub use self::ticker::Ticker;
+#[cfg(feature = "metrics")]
+pub use self::timer::WakeupStats;
+pub use self::timer::{ShutdownGuard, Timer, TimerHandle};
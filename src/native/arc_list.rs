@@ -2,10 +2,17 @@
 
 use std::marker;
 use std::ops::Deref;
-use std::sync::atomic::Ordering::SeqCst;
-use std::sync::atomic::{AtomicBool, AtomicPtr};
 use std::sync::Arc;
 
+// Under `--cfg futures_timer_loom` the atomics are swapped for loom's so the model checker
+// can explore their interleavings. `Arc` stays the standard one since the
+// rest of the crate hands us `std::sync::Arc` nodes (and needs `Weak`, which
+// loom doesn't model).
+#[cfg(futures_timer_loom)]
+use loom::sync::atomic::{AtomicBool, AtomicPtr, Ordering::SeqCst};
+#[cfg(not(futures_timer_loom))]
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering::SeqCst};
+
 pub struct ArcList<T> {
     list: AtomicPtr<Node<T>>,
     _marker: marker::PhantomData<T>,
@@ -36,7 +43,7 @@ impl<T> ArcList<T> {
             // If we've been sealed off, abort and return an error
             if head == Node::SEALED {
                 unsafe {
-                    drop(Arc::from_raw(node as *mut Node<T>));
+                    drop(Arc::from_raw(node));
                 }
                 return Err(());
             }
@@ -50,6 +57,12 @@ impl<T> ArcList<T> {
         }
     }
 
+    /// Returns whether nothing is currently enqueued in this list.
+    pub fn is_empty(&self) -> bool {
+        let head = self.list.load(SeqCst);
+        head == Node::EMPTY || head == Node::SEALED
+    }
+
     /// Atomically empties this list, returning a new owned copy which can be
     /// used to iterate over the entries.
     pub fn take(&self) -> ArcList<T> {
@@ -84,22 +97,33 @@ impl<T> ArcList<T> {
     /// Removes the head of the list of nodes, returning `None` if this is an
     /// empty list.
     pub fn pop(&mut self) -> Option<Arc<Node<T>>> {
-        let head = *self.list.get_mut();
+        // We have exclusive access here, but go through `load`/`store` rather
+        // than `get_mut` since loom's atomics don't offer the latter.
+        let head = self.list.load(SeqCst);
         if head == Node::EMPTY || head == Node::SEALED {
             return None;
         }
         let head = unsafe { Arc::from_raw(head as *const Node<T>) };
-        *self.list.get_mut() = head.next.load(SeqCst);
+        self.list.store(head.next.load(SeqCst), SeqCst);
         // At this point, the node is out of the list, so store `false` so we
         // can enqueue it again and see further changes.
-        assert!(head.enqueued.swap(false, SeqCst));
+        //
+        // `push` only links in a node after flipping its `enqueued` flag from
+        // `false` to `true`, and only `pop` clears it again, so every node in
+        // the list must have the flag set. That's what guarantees a node is in
+        // at most one list at a time, owning exactly one strong reference. If
+        // it's somehow clear the node has still been unlinked correctly, so
+        // rather than tearing down the timer thread this is only checked in
+        // debug builds.
+        let was_enqueued = head.enqueued.swap(false, SeqCst);
+        debug_assert!(was_enqueued, "popped a node which wasn't enqueued");
         Some(head)
     }
 }
 
 impl<T> Drop for ArcList<T> {
     fn drop(&mut self) {
-        while let Some(_) = self.pop() {
+        while self.pop().is_some() {
             // ...
         }
     }
@@ -133,7 +157,7 @@ impl<T> Deref for Node<T> {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, not(futures_timer_loom)))]
 mod tests {
     use super::*;
 
@@ -159,4 +183,116 @@ mod tests {
         assert!(a.take().pop().is_none());
         assert!(a.take_and_seal().pop().is_none());
     }
+
+    #[test]
+    fn concurrent_push_take() {
+        use std::thread;
+
+        let a = Arc::new(ArcList::new());
+        let nodes = (0..4).map(|i| Arc::new(Node::new(i))).collect::<Vec<_>>();
+        let threads = nodes
+            .iter()
+            .map(|n| {
+                let (a, n) = (a.clone(), n.clone());
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        a.push(&n).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        loop {
+            let done = threads.iter().all(|t| t.is_finished());
+            let mut l = a.take();
+            while let Some(n) = l.pop() {
+                assert!(**n < 4);
+            }
+            if done {
+                break;
+            }
+        }
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        // Every reference the list took has been handed back.
+        assert!(a.take_and_seal().pop().is_none());
+        for n in nodes {
+            assert_eq!(Arc::strong_count(&n), 1);
+        }
+    }
+}
+
+#[cfg(all(test, futures_timer_loom))]
+mod loom_tests {
+    use super::*;
+
+    use loom::thread;
+
+    #[test]
+    fn concurrent_push_take() {
+        loom::model(|| {
+            let a = Arc::new(ArcList::new());
+            let n1 = Arc::new(Node::new(1));
+            let n2 = Arc::new(Node::new(2));
+
+            let t = thread::spawn({
+                let (a, n2) = (a.clone(), n2.clone());
+                move || a.push(&n2).unwrap()
+            });
+            a.push(&n1).unwrap();
+            let mut seen = 0;
+            let mut l = a.take();
+            while let Some(n) = l.pop() {
+                seen += **n;
+            }
+            t.join().unwrap();
+
+            let mut l = a.take();
+            while let Some(n) = l.pop() {
+                seen += **n;
+            }
+            assert_eq!(seen, 3);
+        });
+    }
+
+    #[test]
+    fn push_same_node_enqueues_once() {
+        loom::model(|| {
+            let a = Arc::new(ArcList::new());
+            let n = Arc::new(Node::new(1));
+
+            let t = thread::spawn({
+                let (a, n) = (a.clone(), n.clone());
+                move || a.push(&n).unwrap()
+            });
+            a.push(&n).unwrap();
+            t.join().unwrap();
+
+            let mut l = a.take();
+            assert!(l.pop().is_some());
+            assert!(l.pop().is_none());
+        });
+    }
+
+    #[test]
+    fn push_races_seal() {
+        loom::model(|| {
+            let a = Arc::new(ArcList::new());
+            let n = Arc::new(Node::new(1));
+
+            let t = thread::spawn({
+                let (a, n) = (a.clone(), n.clone());
+                move || a.push(&n)
+            });
+            let mut sealed = a.take_and_seal();
+            let pushed = t.join().unwrap();
+
+            // Either the push made it in before the seal and we own it now,
+            // or it was refused; it's never lost in between.
+            assert_eq!(sealed.pop().is_some(), pushed.is_ok());
+            assert!(a.take().pop().is_none());
+        });
+    }
 }
@@ -1,9 +1,24 @@
-use core::cell::UnsafeCell;
 use core::fmt;
-use core::sync::atomic::AtomicUsize;
-use core::sync::atomic::Ordering::{AcqRel, Acquire, Release};
+use core::panic::{RefUnwindSafe, UnwindSafe};
 use core::task::Waker;
 
+#[cfg(futures_timer_loom)]
+use loom::{
+    cell::UnsafeCell,
+    sync::atomic::{
+        AtomicUsize,
+        Ordering::{AcqRel, Acquire, Release},
+    },
+};
+#[cfg(not(futures_timer_loom))]
+use {
+    self::cell::UnsafeCell,
+    core::sync::atomic::{
+        AtomicUsize,
+        Ordering::{AcqRel, Acquire, Release},
+    },
+};
+
 /// A synchronization primitive for task wakeup.
 ///
 /// Sometimes the task interested in a given event will change over time.
@@ -42,8 +57,8 @@ impl AtomicWaker {
     /// Create an `AtomicWaker`.
     pub fn new() -> AtomicWaker {
         // Make sure that task is Sync
-        trait AssertSync: Sync {}
-        impl AssertSync for Waker {}
+        fn assert_sync<T: Sync>() {}
+        assert_sync::<Waker>();
 
         AtomicWaker {
             state: AtomicUsize::new(WAITING),
@@ -104,11 +119,20 @@ impl AtomicWaker {
     /// }
     /// ```
     pub fn register(&self, waker: &Waker) {
-        match self.state.compare_and_swap(WAITING, REGISTERING, Acquire) {
+        // Cloning a waker runs arbitrary code which may panic, so do it before
+        // taking the lock rather than while holding it.
+        let waker = waker.clone();
+        match self
+            .state
+            .compare_exchange(WAITING, REGISTERING, Acquire, Acquire)
+            .unwrap_or_else(|x| x)
+        {
             WAITING => {
                 unsafe {
-                    // Locked acquired, update the waker cell
-                    *self.waker.get() = Some(waker.clone());
+                    // Locked acquired, update the waker cell. The old waker is
+                    // only dropped at the end of this block, once the lock has
+                    // been released, as its `drop` may panic too.
+                    let _old = self.waker.with_mut(|w| (*w).replace(waker));
 
                     // Release the lock. If the state transitioned to include
                     // the `WAKING` bit, this means that a wake has been
@@ -132,7 +156,7 @@ impl AtomicWaker {
 
                             // Take the waker to wake once the atomic operation has
                             // completed.
-                            let waker = (*self.waker.get()).take().unwrap();
+                            let waker = self.waker.with_mut(|w| (*w).take()).unwrap();
 
                             // Just swap, because no one could change state while state == `REGISTERING` | `WAKING`.
                             self.state.swap(WAITING, AcqRel);
@@ -148,7 +172,7 @@ impl AtomicWaker {
                 // Currently in the process of waking the task, i.e.,
                 // `wake` is currently being called on the old task handle.
                 // So, we call wake on the new waker
-                waker.wake_by_ref();
+                waker.wake();
             }
             state => {
                 // In this case, a concurrent thread is holding the
@@ -187,7 +211,7 @@ impl AtomicWaker {
         match self.state.fetch_or(WAKING, AcqRel) {
             WAITING => {
                 // The waking lock has been acquired.
-                let waker = unsafe { (*self.waker.get()).take() };
+                let waker = unsafe { self.waker.with_mut(|w| (*w).take()) };
 
                 // Release the lock
                 self.state.fetch_and(!WAKING, Release);
@@ -225,3 +249,141 @@ impl fmt::Debug for AtomicWaker {
 
 unsafe impl Send for AtomicWaker {}
 unsafe impl Sync for AtomicWaker {}
+
+// The waker cell is only accessed while holding the `REGISTERING` or `WAKING`
+// lock in `state`, and no waker code, which might panic, runs while either is
+// held, so a panic can't leave the lock held or the cell torn.
+impl UnwindSafe for AtomicWaker {}
+impl RefUnwindSafe for AtomicWaker {}
+
+/// A `core::cell::UnsafeCell` with the closure-based access loom's version
+/// has, so the code above reads the same with or without `--cfg futures_timer_loom`.
+#[cfg(not(futures_timer_loom))]
+mod cell {
+    pub struct UnsafeCell<T>(core::cell::UnsafeCell<T>);
+
+    impl<T> UnsafeCell<T> {
+        pub fn new(data: T) -> UnsafeCell<T> {
+            UnsafeCell(core::cell::UnsafeCell::new(data))
+        }
+
+        pub fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+            f(self.0.get())
+        }
+    }
+}
+
+#[cfg(all(test, not(futures_timer_loom)))]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+    use std::sync::Arc;
+    use std::task::{RawWaker, RawWakerVTable, Waker};
+    use std::{mem, panic, ptr};
+
+    use futures::task::{waker, ArcWake};
+
+    use super::AtomicWaker;
+
+    struct Count(AtomicUsize);
+
+    impl ArcWake for Count {
+        fn wake_by_ref(me: &Arc<Self>) {
+            me.0.fetch_add(1, SeqCst);
+        }
+    }
+
+    #[test]
+    fn take_detaches_waker() {
+        let count = Arc::new(Count(AtomicUsize::new(0)));
+        let aw = AtomicWaker::new();
+        aw.register(&waker(count.clone()));
+
+        let taken = aw.take().unwrap();
+        aw.wake();
+        assert_eq!(count.0.load(SeqCst), 0);
+        assert!(aw.take().is_none());
+
+        taken.wake();
+        assert_eq!(count.0.load(SeqCst), 1);
+    }
+
+    #[test]
+    fn panicking_drop_releases_lock() {
+        fn clone(p: *const ()) -> RawWaker {
+            RawWaker::new(p, &VTABLE)
+        }
+        fn noop(_: *const ()) {}
+        fn drop(_: *const ()) {
+            panic!("waker dropped");
+        }
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, drop);
+
+        let bad = unsafe { Waker::from_raw(RawWaker::new(ptr::null(), &VTABLE)) };
+        let aw = AtomicWaker::new();
+        aw.register(&bad);
+        mem::forget(bad);
+
+        // Replacing the bad waker drops it, which panics, but only once the
+        // new waker is in place and the lock released.
+        let count = Arc::new(Count(AtomicUsize::new(0)));
+        let good = waker(count.clone());
+        assert!(panic::catch_unwind(|| aw.register(&good)).is_err());
+        aw.wake();
+        assert_eq!(count.0.load(SeqCst), 1);
+
+        aw.register(&good);
+        aw.wake();
+        assert_eq!(count.0.load(SeqCst), 2);
+    }
+}
+
+#[cfg(all(test, futures_timer_loom))]
+mod loom_tests {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use loom::sync::atomic::{AtomicBool, Ordering::SeqCst};
+    use loom::sync::Arc;
+    use loom::thread;
+
+    use super::AtomicWaker;
+
+    /// Completes once `flag` is set, relying on `waker` to be woken after.
+    struct WaitFor {
+        waker: Arc<AtomicWaker>,
+        flag: Arc<AtomicBool>,
+    }
+
+    impl Future for WaitFor {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            self.waker.register(cx.waker());
+            if self.flag.load(SeqCst) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        }
+    }
+
+    #[test]
+    fn register_wake() {
+        loom::model(|| {
+            let waker = Arc::new(AtomicWaker::new());
+            let flag = Arc::new(AtomicBool::new(false));
+
+            let t = thread::spawn({
+                let (waker, flag) = (waker.clone(), flag.clone());
+                move || {
+                    flag.store(true, SeqCst);
+                    waker.wake();
+                }
+            });
+            // loom reports a deadlock if the wakeup is ever lost
+            loom::future::block_on(WaitFor { waker, flag });
+            t.join().unwrap();
+        });
+    }
+}
@@ -0,0 +1,159 @@
+//! Delays rounded to a coarse grid, sharing one timer per grid point.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::{Duration, Instant};
+
+use super::Delay;
+
+/// A source of delays whose deadlines are rounded up to a fixed granularity.
+///
+/// All delays from a `CoarseTimer` which round to the same deadline share one
+/// underlying `Delay`, and are woken together when it fires. This trades
+/// precision for far fewer timers when scheduling large numbers of similar
+/// timeouts, such as an idle timeout per connection. Delays never fire early,
+/// but may fire up to `granularity` late.
+pub struct CoarseTimer {
+    granularity: Duration,
+    origin: Instant,
+
+    // Keyed by the number of `granularity` steps from `origin` to the
+    // bucket's deadline, so the buckets which have expired are at the front.
+    buckets: Mutex<BTreeMap<u128, Weak<Bucket>>>,
+}
+
+/// A future returned by `CoarseTimer::delay`.
+pub struct CoarseDelay {
+    bucket: Arc<Bucket>,
+}
+
+struct Bucket {
+    delay: Mutex<Delay>,
+    waiters: Arc<Waiters>,
+}
+
+/// Wakes every task waiting on a bucket when the bucket's delay fires.
+struct Waiters(Mutex<Vec<Waker>>);
+
+impl CoarseTimer {
+    /// Creates a new timer which rounds deadlines up to multiples of
+    /// `granularity`.
+    pub fn new(granularity: Duration) -> CoarseTimer {
+        CoarseTimer {
+            granularity: granularity.max(Duration::from_nanos(1)),
+            origin: Instant::now(),
+            buckets: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Creates a future which fires `dur` from now, rounded up to this
+    /// timer's granularity.
+    pub fn delay(&self, dur: Duration) -> CoarseDelay {
+        let now = Instant::now();
+        let deadline = now + dur;
+        let granularity = self.granularity.as_nanos();
+        let index = deadline
+            .duration_since(self.origin)
+            .as_nanos()
+            .div_ceil(granularity);
+        // A deadline rounded beyond what an `Instant` can represent is left
+        // as it is.
+        let at = from_nanos(index * granularity)
+            .and_then(|offset| self.origin.checked_add(offset))
+            .unwrap_or(deadline);
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if let Some(bucket) = buckets.get(&index).and_then(Weak::upgrade) {
+            return CoarseDelay { bucket };
+        }
+
+        // Forget buckets whose deadline has passed, as no new delay can round
+        // to them. Any delays still using them keep them alive themselves.
+        let expired = now.duration_since(self.origin).as_nanos() / granularity;
+        while let Some(entry) = buckets.first_entry() {
+            if *entry.key() > expired {
+                break;
+            }
+            entry.remove();
+        }
+        let bucket = Arc::new(Bucket {
+            delay: Mutex::new(Delay::new_at(at)),
+            waiters: Arc::new(Waiters(Mutex::new(Vec::new()))),
+        });
+        buckets.insert(index, Arc::downgrade(&bucket));
+        CoarseDelay { bucket }
+    }
+
+    /// Returns the number of distinct deadlines with live delays, each of
+    /// which is backed by a single timer.
+    pub fn len(&self) -> usize {
+        let buckets = self.buckets.lock().unwrap();
+        buckets.values().filter(|b| b.strong_count() > 0).count()
+    }
+
+    /// Returns `true` if there are no live delays from this timer.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Converts a number of nanoseconds to a `Duration`, if it fits in one.
+fn from_nanos(nanos: u128) -> Option<Duration> {
+    const NANOS_PER_SEC: u128 = 1_000_000_000;
+    let secs = u64::try_from(nanos / NANOS_PER_SEC).ok()?;
+    let subsec = u32::try_from(nanos % NANOS_PER_SEC).ok()?;
+    Some(Duration::new(secs, subsec))
+}
+
+impl Future for CoarseDelay {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // Join the waiters before checking the delay, so a fire racing with
+        // us can't be missed.
+        {
+            let mut wakers = self.bucket.waiters.0.lock().unwrap();
+            if !wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                wakers.push(cx.waker().clone());
+            }
+        }
+        let waker = Waker::from(self.bucket.waiters.clone());
+        if self.bucket.delay.lock().unwrap().check(&waker) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Wake for Waiters {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let wakers = std::mem::take(&mut *self.0.lock().unwrap());
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+impl fmt::Debug for CoarseTimer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("CoarseTimer")
+            .field("granularity", &self.granularity)
+            .finish()
+    }
+}
+
+impl fmt::Debug for CoarseDelay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("CoarseDelay").finish()
+    }
+}
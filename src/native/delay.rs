@@ -3,14 +3,27 @@
 //! This module contains the `Delay` type which is a future that will resolve
 //! at a particular point in the future.
 
+use std::cmp::Ordering;
+use std::error::Error;
 use std::fmt;
-use std::future::Future;
+use std::future::{self, Future};
 use std::pin::Pin;
-use std::sync::atomic::AtomicUsize;
-use std::sync::atomic::Ordering::SeqCst;
-use std::sync::{Arc, Mutex};
-use std::task::{Context, Poll};
-use std::time::{Duration, Instant};
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering::{Relaxed, SeqCst};
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant, SystemTime};
+
+#[cfg(futures_timer_loom)]
+use loom::sync::{
+    atomic::{AtomicBool, AtomicUsize},
+    Mutex,
+};
+#[cfg(not(futures_timer_loom))]
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize},
+    Mutex,
+};
 
 use super::arc_list::Node;
 use super::AtomicWaker;
@@ -23,10 +36,59 @@ use super::{ScheduledTimer, TimerHandle};
 /// Note that these futures are not intended for high resolution timers, but rather they will
 /// likely fire some granularity after the exact instant that they're otherwise indicated to fire
 /// at.
+///
+/// Cloning a `Delay` allocates and registers a new timer with the same
+/// deadline on the same timer, rather than sharing the existing one.
+///
+/// Delays compare by their deadlines, so they can be kept sorted or pushed
+/// into a `BinaryHeap` directly. Two delays comparing equal only means they
+/// share a deadline, not that they're the same timer.
+///
+/// A `Delay` is `Send` and `Sync`, so it can be moved to or shared with other
+/// threads freely; all of its state shared with the timer is atomic or behind
+/// a lock.
+///
+/// # System suspend
+///
+/// Deadlines are measured with `Instant`, and whether time spent with the
+/// system suspended counts towards them depends on the platform's monotonic
+/// clock. Either way the timer only ever parks until its earliest deadline and re-reads the
+/// clock whenever it wakes, so after a resume every delay whose deadline has
+/// passed fires together, and a long park never holds up a delay created with
+/// an earlier deadline in the meantime. Deadlines which must track wall-clock
+/// time across a suspend should be re-derived from `SystemTime` after resuming,
+/// for example with `Delay::until_system_time`.
 pub struct Delay {
     state: Option<Arc<Node<ScheduledTimer>>>,
+    when: Instant,
+    paused: Option<Duration>,
+
+    // Delays created with a deadline that had already passed aren't
+    // registered with a timer. They resolve immediately, and keep the handle
+    // here so they can be registered if they're ever reset.
+    elapsed: Option<Elapsed>,
+
+    // Whether `poll` may resolve once the deadline has passed by the clock,
+    // without waiting for the timer to fire us.
+    check_clock: bool,
+
+    id: DelayId,
 }
 
+/// What a `Delay` which had already elapsed when it was created keeps in
+/// place of a registration.
+struct Elapsed {
+    handle: TimerHandle,
+
+    // Registered on demand, already fired, once something like a
+    // `DelayCanceller` needs a node to refer to. A reset then carries on with
+    // this node rather than a fresh one, so those handles keep working.
+    node: Mutex<Option<Arc<Node<ScheduledTimer>>>>,
+}
+
+/// Source of `DelayId`s, which only need to be unique.
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
 impl Delay {
     /// Creates a new future which will fire at `dur` time into the future.
     ///
@@ -34,52 +96,418 @@ impl Delay {
     /// The default timer will be spun up in a helper thread on first use.
     #[inline]
     pub fn new(dur: Duration) -> Delay {
-        Delay::new_handle(Instant::now() + dur, Default::default())
+        Delay::new_at(Instant::now() + dur)
+    }
+
+    /// Creates a new future which will fire at the time specified by `at`.
+    ///
+    /// The returned object will be bound to the default timer for this thread.
+    /// The default timer will be spun up in a helper thread on first use.
+    #[inline]
+    pub fn new_at(at: Instant) -> Delay {
+        Delay::new_at_handle(at, Default::default())
+    }
+
+    /// Creates a new future which will fire at the wall-clock time `t`.
+    ///
+    /// The deadline is converted to an `Instant` once, when the delay is
+    /// created, so later changes to the system clock don't move it. If `t` is
+    /// at or before the current system time the delay fires immediately.
+    pub fn until_system_time(t: SystemTime) -> Delay {
+        Delay::new(t.duration_since(SystemTime::now()).unwrap_or_default())
+    }
+
+    /// Creates a new future which will fire at `dur` time into the future.
+    ///
+    /// The returned instance of `Delay` will be bound to the timer specified by
+    /// the `handle` argument.
+    #[inline]
+    pub fn new_handle(dur: Duration, handle: TimerHandle) -> Delay {
+        Delay::new_at_handle(Instant::now() + dur, handle)
     }
 
     /// Creates a new future which will fire at the time specified by `at`.
     ///
     /// The returned instance of `Delay` will be bound to the timer specified by
     /// the `handle` argument.
-    pub(crate) fn new_handle(at: Instant, handle: TimerHandle) -> Delay {
-        let inner = match handle.inner.upgrade() {
-            Some(i) => i,
-            None => return Delay { state: None },
+    ///
+    /// If `at` has already passed then the returned `Delay` is not registered
+    /// with the timer at all and resolves on its first poll.
+    pub fn new_at_handle(at: Instant, handle: TimerHandle) -> Delay {
+        if at <= Instant::now() && handle.is_alive() {
+            return Delay::unregistered(at, Some(handle));
+        }
+        Delay::new_registered(at, handle)
+    }
+
+    /// Creates a delay registered with the timer behind `handle`, even if
+    /// `at` has already passed.
+    pub(crate) fn new_registered(at: Instant, handle: TimerHandle) -> Delay {
+        Delay::registered(at, handle, Start::Scheduled)
+    }
+
+    fn registered(at: Instant, handle: TimerHandle, start: Start) -> Delay {
+        // If we fail to actually register then we've become an inert timer,
+        // meaning that we'll want to immediately return an error from `poll`.
+        let state = match register(&handle, at, start) {
+            Some(state) => state,
+            None => return Delay::unregistered(at, None),
         };
-        let state = Arc::new(Node::new(ScheduledTimer {
-            at: Mutex::new(Some(at)),
-            state: AtomicUsize::new(0),
-            waker: AtomicWaker::new(),
-            inner: handle.inner,
-            slot: Mutex::new(None),
-        }));
+        trace!(deadline = ?at, "delay created");
+        Delay {
+            state: Some(state),
+            when: at,
+            paused: None,
+            elapsed: None,
+            check_clock: true,
+            id: DelayId(NEXT_ID.fetch_add(1, Relaxed)),
+        }
+    }
 
-        // If we fail to actually push our node then we've become an inert
-        // timer, meaning that we'll want to immediately return an error from
-        // `poll`.
-        if inner.list.push(&state).is_err() {
-            return Delay { state: None };
+    /// Creates a delay which isn't registered with any timer.
+    ///
+    /// With a `handle` the delay has already elapsed and will be registered
+    /// with that handle's timer if it's reset. Without one the delay is inert.
+    fn unregistered(at: Instant, handle: Option<TimerHandle>) -> Delay {
+        Delay {
+            state: None,
+            when: at,
+            paused: None,
+            elapsed: handle.map(|handle| Elapsed {
+                handle,
+                node: Mutex::new(None),
+            }),
+            check_clock: true,
+            id: DelayId(NEXT_ID.fetch_add(1, Relaxed)),
         }
+    }
+
+    /// Makes this delay resolve only once its timer has fired it, even after
+    /// its deadline has passed, for `yield_now`.
+    pub(crate) fn wait_for_timer(&mut self) {
+        self.check_clock = false;
+    }
 
-        inner.waker.wake();
-        Delay { state: Some(state) }
+    /// Resets this timeout to an new timeout which will fire `dur` time into
+    /// the future.
+    #[inline]
+    pub fn reset(&mut self, dur: Duration) {
+        self.reset_at(Instant::now() + dur);
     }
 
     /// Resets this timeout to an new timeout which will fire at the time
     /// specified by `at`.
+    ///
+    /// A task blocked on this delay stays registered across the reset and is
+    /// woken once the new deadline elapses, so there's no need to poll the
+    /// delay again just to keep receiving wakeups.
     #[inline]
-    pub fn reset(&mut self, dur: Duration) {
-        if self._reset(dur).is_err() {
+    pub fn reset_at(&mut self, at: Instant) {
+        trace!(deadline = ?at, "delay reset");
+        if let Some(elapsed) = self.elapsed.take() {
+            match elapsed.node.into_inner().unwrap() {
+                Some(node) => self.state = Some(node),
+                None => {
+                    let id = self.id;
+                    *self = Delay::new_at_handle(at, elapsed.handle);
+                    self.id = id;
+                    return;
+                }
+            }
+        }
+        self.when = at;
+        self.paused = None;
+        if self._reset(at).is_err() {
+            // We're inert now, but still let go of the timer as a drop would,
+            // so its count of live delays stays right.
+            self.release();
             self.state = None
         }
     }
 
-    fn _reset(&mut self, dur: Duration) -> Result<(), ()> {
+    /// Pushes this delay's deadline back by `extra`, relative to its current
+    /// deadline rather than to now.
+    ///
+    /// This suits sliding timeouts, where each bit of activity extends the
+    /// deadline. Like `reset_at`, this keeps any registered task and resumes
+    /// a paused delay.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new deadline can't be represented by an `Instant`.
+    pub fn extend(&mut self, extra: Duration) {
+        let at = self
+            .when
+            .checked_add(extra)
+            .expect("overflow when extending delay");
+        self.reset_at(at);
+    }
+
+    /// Like `reset_at`, but refuses to reset to an instant well in the past.
+    ///
+    /// `reset_at` accepts any instant and a deadline in the past simply fires
+    /// on the next poll, which can turn a miscomputed deadline into a busy
+    /// loop. This method instead returns a `ClockError` if `at` passed more
+    /// than 100 milliseconds ago, leaving the delay untouched. A deadline
+    /// which passed more recently than that, say one computed as
+    /// `Instant::now()` a moment before the call, is clamped to now and fires
+    /// on the next poll.
+    pub fn reset_at_checked(&mut self, at: Instant) -> Result<(), ClockError> {
+        self.reset_at(check_deadline(at)?);
+        Ok(())
+    }
+
+    /// Returns a handle which can be used to cancel this delay from elsewhere.
+    ///
+    /// Cancelling a delay causes it to complete on its next poll as though
+    /// it had fired, waking the task blocked on it, if any.
+    pub fn cancel_handle(&self) -> DelayCanceller {
+        DelayCanceller { state: self.node() }
+    }
+
+    /// Returns a handle which is notified whenever this delay is reset.
+    ///
+    /// Only changes made after this call are reported. Each delay only
+    /// supports one subscriber at a time: if several `ResetNotify` handles
+    /// are waiting, only the one which registered last is woken. An inert
+    /// delay, whose timer has gone away, never reports a reset.
+    pub fn subscribe_resets(&self) -> ResetNotify {
+        let state = self.node();
+        ResetNotify {
+            seen: state.as_ref().map_or(0, |s| s.state.load(SeqCst) >> 2),
+            state,
+        }
+    }
+
+    /// Returns the node shared with this delay's timer, registering one for
+    /// an elapsed delay if it doesn't have one yet.
+    fn node(&self) -> Option<Arc<Node<ScheduledTimer>>> {
+        if let Some(ref state) = self.state {
+            return Some(state.clone());
+        }
+        let elapsed = self.elapsed.as_ref()?;
+        let mut node = elapsed.node.lock().unwrap();
+        if node.is_none() {
+            *node = register(&elapsed.handle, self.when, Start::Elapsed);
+        }
+        node.clone()
+    }
+
+    /// Forces this delay to complete on its next poll, regardless of its
+    /// deadline.
+    ///
+    /// This is mostly useful in tests which want to exercise what happens
+    /// when a delay elapses without actually waiting for it. A paused delay
+    /// is resumed, and this works on an inert delay too, whose timer has gone
+    /// away: it resolves rather than panicking.
+    pub fn fire_immediately(&mut self) {
+        self.paused = None;
+        match self.state {
+            Some(ref state) => {
+                state.mark_fired();
+                state.waker.wake();
+            }
+            // Treat an inert delay as elapsed. Its handle is dead, so a later
+            // reset just leaves it inert again.
+            None if self.elapsed.is_none() => {
+                self.elapsed = Some(Elapsed {
+                    handle: TimerHandle { inner: Weak::new() },
+                    node: Mutex::new(None),
+                })
+            }
+            None => {}
+        }
+    }
+
+    /// Pauses this delay, freezing the time it has left to run.
+    ///
+    /// While paused the delay will not fire. Calling `resume` schedules it
+    /// again with the time that was remaining when it was paused. Pausing a
+    /// delay which has already fired, or which is already paused, does
+    /// nothing.
+    pub fn pause(&mut self) {
+        let state = match self.state {
+            Some(ref state) => state,
+            None => return,
+        };
+        if self.paused.is_some() || state.state.load(SeqCst) & 0b01 != 0 {
+            return;
+        }
+        self.paused = Some(self.when.saturating_duration_since(Instant::now()));
+
+        // Pull the timer out of the heap so it doesn't fire while paused.
+        if let Some(timeouts) = state.inner.upgrade() {
+            *state.at.lock().unwrap() = None;
+            if timeouts.list.push(state).is_ok() {
+                timeouts.waker.wake();
+            }
+        }
+    }
+
+    /// Resumes a delay previously paused with `pause`, scheduling it to fire
+    /// after the time it had remaining.
+    ///
+    /// Resuming a delay which isn't paused does nothing.
+    pub fn resume(&mut self) {
+        if let Some(remaining) = self.paused {
+            self.reset(remaining);
+        }
+    }
+
+    /// Creates a `WeakDelay` which can tell whether this delay is still alive.
+    ///
+    /// Only an inert delay, whose timer has gone away, can't be tracked; the
+    /// returned `WeakDelay` then reports it as gone straight away.
+    pub fn downgrade(&self) -> WeakDelay {
+        WeakDelay {
+            state: self.node().as_ref().map_or_else(Weak::new, Arc::downgrade),
+        }
+    }
+
+    /// Returns the instant this delay was last scheduled to fire at.
+    ///
+    /// This tracks every `reset`, `reset_at`, `extend` and `resume`, and is
+    /// kept even once the delay has fired or its timer has gone away. A
+    /// paused delay reports the deadline it had when it was paused.
+    pub fn deadline(&self) -> Instant {
+        self.when
+    }
+
+    /// Returns an identifier for this delay, usable as a map or set key.
+    ///
+    /// The id is unique among all delays in the process and stays the same
+    /// for this delay's whole life, across resets. Clones are separate
+    /// delays, so they get their own ids.
+    pub fn id(&self) -> DelayId {
+        self.id
+    }
+
+    /// Returns the time left until this delay's deadline, or zero if it has
+    /// passed.
+    ///
+    /// A paused delay reports the time it had left when it was paused.
+    ///
+    /// An `Instant` means nothing outside the process that created it, so
+    /// this is the value to persist for a delay that should survive a
+    /// restart: save `saturating_remaining()` and restore with `Delay::new`
+    /// from the saved duration. Time spent while the process wasn't running
+    /// isn't counted, so subtract it using wall-clock timestamps saved
+    /// alongside if that matters.
+    pub fn saturating_remaining(&self) -> Duration {
+        match self.paused {
+            Some(remaining) => remaining,
+            None => self.when.saturating_duration_since(Instant::now()),
+        }
+    }
+
+    /// Converts this delay into a future which resolves to how late the delay
+    /// fired.
+    ///
+    /// The output is the time elapsed between the deadline and the moment the
+    /// delay was observed to have fired, saturating to zero. This doesn't
+    /// change the output of `Delay` itself.
+    pub async fn into_overshoot(self) -> Duration {
+        let when = self.when;
+        self.await;
+        Instant::now().saturating_duration_since(when)
+    }
+
+    /// Checks whether this delay has fired, registering `waker` to be woken
+    /// when it does if it hasn't.
+    ///
+    /// This is the same as polling the delay, for callers which only have a
+    /// `Waker` to hand rather than a `Context`.
+    pub fn check(&mut self, waker: &Waker) -> bool {
+        let mut cx = Context::from_waker(waker);
+        Pin::new(self).poll(&mut cx).is_ready()
+    }
+
+    /// Returns whichever of this delay and `other` has the earlier deadline.
+    ///
+    /// The later delay is dropped, deregistering it from its timer. If both
+    /// deadlines are equal this delay is kept.
+    pub fn race_with(self, other: Delay) -> Delay {
+        if other.when < self.when {
+            other
+        } else {
+            self
+        }
+    }
+
+    /// Splits this delay into one firing `fraction` of the way through the
+    /// time remaining until its deadline, and this delay itself.
+    ///
+    /// Useful for a warning ahead of a timeout: `split_at(0.8)` gives a delay
+    /// at 80% of the remaining time alongside the original. `fraction` is
+    /// clamped to `[0, 1]`, and both delays share this delay's timer. If the
+    /// deadline has already passed both halves fire immediately.
+    pub fn split_at(self, fraction: f64) -> (Delay, Delay) {
+        let fraction = if fraction.is_nan() {
+            0.0
+        } else {
+            fraction.clamp(0.0, 1.0)
+        };
+        let remaining = self.when.saturating_duration_since(Instant::now());
+        let mut first = self.clone();
+        first.reset_at(self.when - remaining.mul_f64(1.0 - fraction));
+        (first, self)
+    }
+
+    /// Converts this delay into a future which resolves to `value` once the
+    /// delay fires.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[async_std::main]
+    /// # async fn main() {
+    /// use std::time::Duration;
+    /// use futures_timer::Delay;
+    ///
+    /// let tick = Delay::new(Duration::from_secs(1)).map_output("tick").await;
+    /// assert_eq!(tick, "tick");
+    /// # }
+    /// ```
+    pub async fn map_output<T>(self, value: T) -> T {
+        self.await;
+        value
+    }
+
+    /// Waits for this delay, then for a further `next`.
+    ///
+    /// The second wait is only scheduled once this delay has fired, so the
+    /// total is at least the sum of the two even if the first fires late. The
+    /// delay's registration is reused for it.
+    pub async fn then_delay(mut self, next: Duration) {
+        (&mut self).await;
+        self.reset(next);
+        self.await
+    }
+
+    /// Converts this delay into a future which resolves to the instant the
+    /// delay was observed to have fired.
+    ///
+    /// Like `into_overshoot`, this doesn't change the output of `Delay`
+    /// itself.
+    pub async fn timed(self) -> Instant {
+        self.await;
+        Instant::now()
+    }
+
+    fn _reset(&mut self, at: Instant) -> Result<(), ()> {
         let state = match self.state {
             Some(ref state) => state,
             None => return Err(()),
         };
         if let Some(timeouts) = state.inner.upgrade() {
+            // Publish the new deadline before bumping the generation. The
+            // timer may be processing this node concurrently, and if it saw
+            // the new generation paired with the old deadline it would fire
+            // us at the wrong time.
+            *state.at.lock().unwrap() = Some(at);
+            state
+                .seq
+                .store(timeouts.next_seq.fetch_add(1, SeqCst), SeqCst);
             let mut bits = state.state.load(SeqCst);
             loop {
                 // If we've been invalidated, cancel this reset
@@ -88,11 +516,15 @@ impl Delay {
                 }
                 let new = bits.wrapping_add(0b100) & !0b11;
                 match state.state.compare_exchange(bits, new, SeqCst, SeqCst) {
+                    Ok(_) if bits & 0b01 != 0 => {
+                        timeouts.scheduled.fetch_add(1, SeqCst);
+                        break;
+                    }
                     Ok(_) => break,
                     Err(s) => bits = s,
                 }
             }
-            *state.at.lock().unwrap() = Some(Instant::now() + dur);
+            state.reset_waker.wake();
             // If we fail to push our node then we've become an inert timer, so
             // we'll want to clear our `state` field accordingly
             timeouts.list.push(state)?;
@@ -109,11 +541,29 @@ impl Future for Delay {
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let state = match self.state {
             Some(ref state) => state,
+            None if self.elapsed.is_some() => return self.fired(),
             None => panic!("timer has gone away"),
         };
 
-        if state.state.load(SeqCst) & 1 != 0 {
-            return Poll::Ready(());
+        // The timer never fires a paused delay, but it can still be
+        // cancelled.
+        if self.paused.is_some() {
+            if state.state.load(SeqCst) & 1 == 0 {
+                state.waker.register(cx.waker());
+
+                // Check again now that we've registered, in case a cancel
+                // raced with us.
+                if state.state.load(SeqCst) & 1 == 0 {
+                    return Poll::Pending;
+                }
+            }
+            return self.fired();
+        }
+
+        // A deadline that has already passed is ready regardless of whether
+        // the timer has gotten around to firing us yet.
+        if state.state.load(SeqCst) & 1 != 0 || (self.check_clock && Instant::now() >= self.when) {
+            return self.fired();
         }
 
         state.waker.register(cx.waker());
@@ -122,28 +572,382 @@ impl Future for Delay {
         // state. If we've fired the first bit is set, and if we've been
         // invalidated the second bit is set.
         match state.state.load(SeqCst) {
-            n if n & 0b01 != 0 => Poll::Ready(()),
+            n if n & 0b01 != 0 => self.fired(),
             n if n & 0b10 != 0 => panic!("timer has gone away"),
             _ => Poll::Pending,
         }
     }
 }
 
+impl Delay {
+    /// Called whenever `poll` observes that this delay has fired.
+    fn fired(&self) -> Poll<()> {
+        trace!(
+            scheduled = ?self.when,
+            late = ?Instant::now().saturating_duration_since(self.when),
+            "delay fired"
+        );
+        Poll::Ready(())
+    }
+}
+
 impl Drop for Delay {
     fn drop(&mut self) {
+        self.release();
+    }
+}
+
+impl Delay {
+    /// Deregisters this delay from its timer.
+    fn release(&mut self) {
+        if let Some(elapsed) = self.elapsed.take() {
+            self.state = elapsed.node.into_inner().unwrap();
+        }
         let state = match self.state {
             Some(ref s) => s,
             None => return,
         };
+        state.dropped.store(true, SeqCst);
+        // Setting the fired bit settles the race with the timer firing us
+        // concurrently, so exactly one of us takes us off the scheduled count.
+        state.mark_fired();
         if let Some(timeouts) = state.inner.upgrade() {
+            timeouts.live.fetch_sub(1, SeqCst);
             *state.at.lock().unwrap() = None;
-            if timeouts.list.push(state).is_ok() {
-                timeouts.waker.wake();
+            // Even if the timer's been shut down, a `ShutdownGuard` may be
+            // waiting on this.
+            let _ = timeouts.list.push(state);
+            timeouts.waker.wake();
+        }
+    }
+}
+
+/// The state `register` sets a new node up in.
+#[derive(Clone, Copy, PartialEq)]
+enum Start {
+    /// Pushed to the timer to fire at its deadline.
+    Scheduled,
+    /// Counted as scheduled, but kept from the timer until it's reset.
+    Paused,
+    /// Already fired, and only reaches the timer if it's reset.
+    Elapsed,
+}
+
+/// Allocates a node for a delay at `at` on `handle`'s timer and counts it
+/// there, set up according to `start`. Returns `None` if the timer has gone
+/// away.
+fn register(handle: &TimerHandle, at: Instant, start: Start) -> Option<Arc<Node<ScheduledTimer>>> {
+    let inner = handle.inner.upgrade()?;
+    let state = Arc::new(Node::new(ScheduledTimer {
+        at: Mutex::new(if start == Start::Paused {
+            None
+        } else {
+            Some(at)
+        }),
+        seq: AtomicUsize::new(inner.next_seq.fetch_add(1, SeqCst)),
+        state: AtomicUsize::new(if start == Start::Elapsed { 0b01 } else { 0 }),
+        waker: AtomicWaker::new(),
+        reset_waker: AtomicWaker::new(),
+        dropped: AtomicBool::new(false),
+        inner: handle.inner.clone(),
+        slot: Mutex::new(None),
+    }));
+    if start == Start::Scheduled {
+        inner.list.push(&state).ok()?;
+    }
+    if start != Start::Elapsed {
+        inner.scheduled.fetch_add(1, SeqCst);
+    }
+    inner.live.fetch_add(1, SeqCst);
+    inner.waker.wake();
+    Some(state)
+}
+
+/// Identifies a `Delay`, as returned by `Delay::id`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DelayId(u64);
+
+/// A weak reference to a `Delay`, created by `Delay::downgrade`.
+///
+/// This doesn't keep the delay alive or registered with its timer, and gives
+/// no access to the delay itself: it observes whether the delay has been
+/// dropped, and can take a snapshot of its deadline.
+#[derive(Clone)]
+pub struct WeakDelay {
+    state: Weak<Node<ScheduledTimer>>,
+}
+
+impl WeakDelay {
+    /// Returns whether the original delay is still alive.
+    pub fn is_alive(&self) -> bool {
+        self.state
+            .upgrade()
+            .is_some_and(|state| !state.dropped.load(SeqCst))
+    }
+
+    /// Returns a new delay for the original's current deadline, bound to the
+    /// same timer, if the original is still alive.
+    ///
+    /// This is a snapshot, much like cloning the original: the returned
+    /// delay is registered separately, so resetting, cancelling or dropping
+    /// it doesn't affect the original, and later resets of the original
+    /// don't move it. Returns `None` if the original has been dropped, is
+    /// paused, or its timer has gone away.
+    pub fn snapshot(&self) -> Option<Delay> {
+        let state = self.state.upgrade()?;
+        if state.dropped.load(SeqCst) {
+            return None;
+        }
+        let at = (*state.at.lock().unwrap())?;
+        let handle = TimerHandle {
+            inner: state.inner.clone(),
+        };
+        if !handle.is_alive() {
+            return None;
+        }
+        Some(Delay::new_at_handle(at, handle))
+    }
+}
+
+impl fmt::Debug for WeakDelay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("WeakDelay").finish()
+    }
+}
+
+/// How long ago a deadline given to `Delay::reset_at_checked`, or to a
+/// `DelayBuilder` which doesn't fire if past, may have passed before it's
+/// rejected rather than clamped to now.
+const PAST_TOLERANCE: Duration = Duration::from_millis(100);
+
+/// Clamps `at` to now if it has only just passed, or returns a `ClockError`
+/// if it passed longer ago than `PAST_TOLERANCE`.
+fn check_deadline(at: Instant) -> Result<Instant, ClockError> {
+    let now = Instant::now();
+    if at >= now {
+        Ok(at)
+    } else if now - at <= PAST_TOLERANCE {
+        Ok(now)
+    } else {
+        Err(ClockError(()))
+    }
+}
+
+/// Error returned by `Delay::reset_at_checked` when asked to reset to an
+/// instant which passed too long ago to be clamped to now, and by `DelayBuilder::build` when asked
+/// to reject one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClockError(());
+
+impl fmt::Display for ClockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("deadline is in the past")
+    }
+}
+
+impl Error for ClockError {}
+
+/// Configures and creates a `Delay`.
+///
+/// `Delay::new` and friends cover the common cases; the builder collects the
+/// less common knobs in one place. Without a deadline the built delay fires
+/// immediately, and without a handle it's bound to the global timer.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use futures_timer::DelayBuilder;
+///
+/// let delay = DelayBuilder::new()
+///     .after(Duration::from_secs(1))
+///     .build()
+///     .unwrap();
+/// # drop(delay);
+/// ```
+#[derive(Debug)]
+pub struct DelayBuilder {
+    deadline: Option<Deadline>,
+    handle: Option<TimerHandle>,
+    fire_if_past: bool,
+}
+
+#[derive(Debug)]
+enum Deadline {
+    At(Instant),
+    After(Duration),
+}
+
+impl DelayBuilder {
+    /// Creates a new builder with the default settings.
+    pub fn new() -> DelayBuilder {
+        DelayBuilder::default()
+    }
+
+    /// Sets the instant at which the delay fires.
+    pub fn deadline(mut self, at: Instant) -> DelayBuilder {
+        self.deadline = Some(Deadline::At(at));
+        self
+    }
+
+    /// Sets the delay to fire `dur` after it's built.
+    pub fn after(mut self, dur: Duration) -> DelayBuilder {
+        self.deadline = Some(Deadline::After(dur));
+        self
+    }
+
+    /// Sets the timer the delay is registered with.
+    pub fn handle(mut self, handle: TimerHandle) -> DelayBuilder {
+        self.handle = Some(handle);
+        self
+    }
+
+    /// Sets whether a `deadline` which has already passed is accepted, firing
+    /// the delay immediately, or rejected by `build`.
+    ///
+    /// The default is `true`, matching `Delay::new_at`. Durations given to
+    /// `after` can't be in the past, so this only affects `deadline`.
+    pub fn fire_if_past(mut self, fire: bool) -> DelayBuilder {
+        self.fire_if_past = fire;
+        self
+    }
+
+    /// Creates the delay.
+    ///
+    /// Returns a `ClockError` if `fire_if_past(false)` was set and the
+    /// deadline passed too long ago; one which has only just passed is
+    /// clamped to now, as with `Delay::reset_at_checked`.
+    pub fn build(self) -> Result<Delay, ClockError> {
+        let at = match self.deadline {
+            Some(Deadline::At(at)) if !self.fire_if_past => check_deadline(at)?,
+            Some(Deadline::At(at)) => at,
+            Some(Deadline::After(dur)) => Instant::now() + dur,
+            None => Instant::now(),
+        };
+        Ok(Delay::new_at_handle(at, self.handle.unwrap_or_default()))
+    }
+}
+
+impl Default for DelayBuilder {
+    fn default() -> DelayBuilder {
+        DelayBuilder {
+            deadline: None,
+            handle: None,
+            fire_if_past: true,
+        }
+    }
+}
+
+/// A handle to cancel a `Delay`, created by `Delay::cancel_handle`.
+#[derive(Clone)]
+pub struct DelayCanceller {
+    state: Option<Arc<Node<ScheduledTimer>>>,
+}
+
+impl DelayCanceller {
+    /// Cancels the delay, causing it to complete immediately, even if it's
+    /// paused.
+    ///
+    /// This has no effect if the delay has already fired or is inert.
+    pub fn cancel(&self) {
+        if let Some(ref state) = self.state {
+            trace!("delay cancelled");
+            state.mark_fired();
+            state.waker.wake();
+        }
+    }
+}
+
+impl fmt::Debug for DelayCanceller {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("DelayCanceller").finish()
+    }
+}
+
+/// A handle notified whenever a `Delay` is reset, created by
+/// `Delay::subscribe_resets`.
+pub struct ResetNotify {
+    state: Option<Arc<Node<ScheduledTimer>>>,
+    seen: usize,
+}
+
+impl ResetNotify {
+    /// Polls for a reset of the delay since the last one this handle saw.
+    ///
+    /// Returns `Poll::Ready` once per batch of resets, registering the current
+    /// task to be woken by the next reset otherwise.
+    pub fn poll_changed(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let state = match self.state {
+            Some(ref state) => state,
+            None => return Poll::Pending,
+        };
+        if state.state.load(SeqCst) >> 2 == self.seen {
+            state.reset_waker.register(cx.waker());
+
+            // Check again now that we've registered, in case a reset raced
+            // with us.
+            if state.state.load(SeqCst) >> 2 == self.seen {
+                return Poll::Pending;
+            }
+        }
+        self.seen = state.state.load(SeqCst) >> 2;
+        Poll::Ready(())
+    }
+
+    /// Waits for the delay to be reset.
+    pub async fn changed(&mut self) {
+        future::poll_fn(|cx| self.poll_changed(cx)).await
+    }
+}
+
+impl fmt::Debug for ResetNotify {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("ResetNotify").finish()
+    }
+}
+
+impl Clone for Delay {
+    fn clone(&self) -> Delay {
+        match self.state {
+            Some(ref state) => {
+                let handle = TimerHandle {
+                    inner: state.inner.clone(),
+                };
+                if self.paused.is_none() {
+                    return Delay::new_at_handle(self.when, handle);
+                }
+                // A paused delay's deadline may well have passed, but the
+                // clone has to stay paused too, so it's registered without
+                // ever being handed to the timer.
+                let mut delay = Delay::registered(self.when, handle, Start::Paused);
+                delay.paused = self.paused;
+                delay
             }
+            None => Delay::unregistered(self.when, self.elapsed.as_ref().map(|e| e.handle.clone())),
         }
     }
 }
 
+impl PartialEq for Delay {
+    fn eq(&self, other: &Delay) -> bool {
+        self.when == other.when
+    }
+}
+
+impl Eq for Delay {}
+
+impl PartialOrd for Delay {
+    fn partial_cmp(&self, other: &Delay) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Delay {
+    fn cmp(&self, other: &Delay) -> Ordering {
+        self.when.cmp(&other.when)
+    }
+}
+
 impl fmt::Debug for Delay {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         f.debug_struct("Delay").finish()
@@ -0,0 +1,225 @@
+//! A queue of values with deadlines, all driven by a single `Delay`.
+
+use std::fmt;
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use super::heap::{Heap, Slot, TimerEntry};
+use super::Delay;
+
+/// A queue of values, each with a deadline, which yields values as their
+/// deadlines elapse.
+///
+/// Inserting a value returns a `Key` which can later be used to reset its
+/// deadline or remove it from the queue early. Like `DelaySet`, all entries
+/// share a single `Delay` armed for the earliest deadline.
+///
+/// Keys are reused once their value has left the queue, so a key must not be
+/// used after its value has been removed or has expired.
+pub struct DelayQueue<T> {
+    heap: Heap<TimerEntry<Key>>,
+    entries: Vec<Option<Stored<T>>>,
+    free: Vec<usize>,
+    len: usize,
+    delay: Option<Delay>,
+    armed: Option<Instant>,
+}
+
+struct Stored<T> {
+    value: T,
+    at: Instant,
+    slot: Slot,
+}
+
+/// A token identifying a value inserted into a `DelayQueue`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Key(usize);
+
+/// A value which has left a `DelayQueue`, either because its deadline elapsed
+/// or because it was removed.
+#[derive(Debug)]
+pub struct Expired<T> {
+    value: T,
+    deadline: Instant,
+    key: Key,
+}
+
+impl<T> Expired<T> {
+    /// Returns a reference to the value.
+    pub fn get_ref(&self) -> &T {
+        &self.value
+    }
+
+    /// Returns a mutable reference to the value.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    /// Consumes this `Expired`, returning the value.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// Returns the deadline the value had when it left the queue.
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Returns the key the value was stored under.
+    pub fn key(&self) -> Key {
+        self.key
+    }
+}
+
+impl<T> DelayQueue<T> {
+    /// Creates a new, empty queue.
+    pub fn new() -> DelayQueue<T> {
+        DelayQueue {
+            heap: Heap::new(),
+            entries: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+            delay: None,
+            armed: None,
+        }
+    }
+
+    /// Inserts `value` to expire `dur` from now, returning its key.
+    pub fn insert(&mut self, value: T, dur: Duration) -> Key {
+        self.insert_at(value, Instant::now() + dur)
+    }
+
+    /// Inserts `value` to expire at `deadline`, returning its key.
+    pub fn insert_at(&mut self, value: T, deadline: Instant) -> Key {
+        let key = match self.free.pop() {
+            Some(idx) => Key(idx),
+            None => {
+                self.entries.push(None);
+                Key(self.entries.len() - 1)
+            }
+        };
+        let slot = self.heap.push(TimerEntry::new(deadline, key));
+        self.entries[key.0] = Some(Stored {
+            value,
+            at: deadline,
+            slot,
+        });
+        self.len += 1;
+        key
+    }
+
+    /// Resets the value stored under `key` to expire `dur` from now.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` isn't in the queue.
+    pub fn reset(&mut self, key: &Key, dur: Duration) {
+        self.reset_at(key, Instant::now() + dur)
+    }
+
+    /// Resets the value stored under `key` to expire at `deadline`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` isn't in the queue.
+    pub fn reset_at(&mut self, key: &Key, deadline: Instant) {
+        let stored = self
+            .entries
+            .get_mut(key.0)
+            .and_then(|e| e.as_mut())
+            .expect("invalid key");
+        let slot = self.heap.push(TimerEntry::new(deadline, *key));
+        self.heap.remove(mem::replace(&mut stored.slot, slot));
+        stored.at = deadline;
+    }
+
+    /// Removes the value stored under `key`, returning it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` isn't in the queue.
+    pub fn remove(&mut self, key: &Key) -> Expired<T> {
+        let stored = self.take(key);
+        self.heap.remove(stored.slot);
+        Expired {
+            value: stored.value,
+            deadline: stored.at,
+            key: *key,
+        }
+    }
+
+    /// Returns the number of values in the queue.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the queue contains no values.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Polls for the next value whose deadline has elapsed.
+    ///
+    /// Values are returned one at a time in deadline order. Returns
+    /// `Poll::Ready(None)` if the queue is empty, and otherwise
+    /// `Poll::Pending` until the earliest deadline elapses, at which point the
+    /// current task will be woken.
+    pub fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<Option<Expired<T>>> {
+        let at = match self.heap.peek() {
+            Some(head) => head.at,
+            None => return Poll::Ready(None),
+        };
+
+        if at > Instant::now() {
+            // Make sure our delay is armed for the earliest deadline, then
+            // wait for it to fire.
+            if self.armed != Some(at) {
+                match self.delay {
+                    Some(ref mut delay) => delay.reset_at(at),
+                    None => self.delay = Some(Delay::new_at(at)),
+                }
+                self.armed = Some(at);
+            }
+            let delay = self.delay.as_mut().unwrap();
+            if Pin::new(delay).poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+
+        let key = self.heap.pop().unwrap().value;
+        let stored = self.take(&key);
+        Poll::Ready(Some(Expired {
+            value: stored.value,
+            deadline: stored.at,
+            key,
+        }))
+    }
+
+    fn take(&mut self, key: &Key) -> Stored<T> {
+        let stored = self
+            .entries
+            .get_mut(key.0)
+            .and_then(|e| e.take())
+            .expect("invalid key");
+        self.free.push(key.0);
+        self.len -= 1;
+        stored
+    }
+}
+
+impl<T> Default for DelayQueue<T> {
+    fn default() -> DelayQueue<T> {
+        DelayQueue::new()
+    }
+}
+
+impl<T> fmt::Debug for DelayQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("DelayQueue")
+            .field("len", &self.len)
+            .finish()
+    }
+}
@@ -0,0 +1,136 @@
+//! A set of keys with deadlines, all driven by a single `Delay`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use super::heap::{Heap, Slot, TimerEntry};
+use super::Delay;
+
+/// A collection of keys, each with a deadline, which yields keys as their
+/// deadlines elapse.
+///
+/// Entries are kept in a timer heap and share a single `Delay` armed for the
+/// earliest deadline, so only one waker is registered and the task is only
+/// woken when that deadline elapses.
+pub struct DelaySet<K> {
+    heap: Heap<TimerEntry<K>>,
+    slots: HashMap<K, Slot>,
+    delay: Option<Delay>,
+    armed: Option<Instant>,
+}
+
+impl<K: Hash + Eq + Clone> DelaySet<K> {
+    /// Creates a new, empty set.
+    pub fn new() -> DelaySet<K> {
+        DelaySet {
+            heap: Heap::new(),
+            slots: HashMap::new(),
+            delay: None,
+            armed: None,
+        }
+    }
+
+    /// Inserts `key` to expire at `deadline`.
+    ///
+    /// If `key` is already present its deadline is replaced.
+    pub fn insert(&mut self, key: K, deadline: Instant) {
+        self.remove(&key);
+        let slot = self.heap.push(TimerEntry::new(deadline, key.clone()));
+        self.slots.insert(key, slot);
+    }
+
+    /// Removes `key` from the set, returning whether it was present.
+    pub fn remove(&mut self, key: &K) -> bool {
+        match self.slots.remove(key) {
+            Some(slot) => {
+                self.heap.remove(slot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the number of keys in the set.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns `true` if the set contains no keys.
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Polls for the next key whose deadline has elapsed.
+    ///
+    /// Keys are returned one at a time in deadline order. Returns
+    /// `Poll::Ready(None)` if the set is empty, and otherwise
+    /// `Poll::Pending` until the earliest deadline elapses, at which point the
+    /// current task will be woken.
+    pub fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<Option<K>> {
+        let at = match self.heap.peek() {
+            Some(head) => head.at,
+            None => return Poll::Ready(None),
+        };
+
+        if at > Instant::now() {
+            // Make sure our delay is armed for the earliest deadline, then
+            // wait for it to fire.
+            if self.armed != Some(at) {
+                match self.delay {
+                    Some(ref mut delay) => delay.reset_at(at),
+                    None => self.delay = Some(Delay::new_at(at)),
+                }
+                self.armed = Some(at);
+            }
+            let delay = self.delay.as_mut().unwrap();
+            if Pin::new(delay).poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+
+        let head = self.heap.pop().unwrap();
+        self.slots.remove(&head.value);
+        Poll::Ready(Some(head.value))
+    }
+
+    /// Polls for every key whose deadline has elapsed, all at once.
+    ///
+    /// This waits like `poll_expired`, but once the earliest deadline has
+    /// elapsed it returns that key along with every other key due by now, in
+    /// deadline order, so keys expiring together cost a single wakeup.
+    /// Returns `Poll::Ready` with an empty vector if the set is empty.
+    pub fn poll_expired_batch(&mut self, cx: &mut Context<'_>) -> Poll<Vec<K>> {
+        let first = match self.poll_expired(cx) {
+            Poll::Ready(Some(key)) => key,
+            Poll::Ready(None) => return Poll::Ready(Vec::new()),
+            Poll::Pending => return Poll::Pending,
+        };
+        let mut batch = vec![first];
+        let now = Instant::now();
+        while self.heap.peek().is_some_and(|head| head.at <= now) {
+            let head = self.heap.pop().unwrap();
+            self.slots.remove(&head.value);
+            batch.push(head.value);
+        }
+        Poll::Ready(batch)
+    }
+}
+
+impl<K: Hash + Eq + Clone> Default for DelaySet<K> {
+    fn default() -> DelaySet<K> {
+        DelaySet::new()
+    }
+}
+
+impl<K> fmt::Debug for DelaySet<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("DelaySet")
+            .field("len", &self.slots.len())
+            .finish()
+    }
+}
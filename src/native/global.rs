@@ -1,16 +1,163 @@
 use std::future::Future;
+use std::hint;
 use std::io;
 use std::mem::{self, ManuallyDrop};
 use std::pin::Pin;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::task::{Context, RawWaker, RawWakerVTable, Waker};
 use std::thread;
 use std::thread::Thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
+use super::timer::IdleWait;
 use super::{Timer, TimerHandle};
 
+/// The driver backing the global fallback timer, if one is running. Unless
+/// one was installed with `set_global_driver` this is a `HelperThread`.
+pub(crate) static HELPER: Mutex<Option<Box<dyn TimerDriver>>> = Mutex::new(None);
+
+/// Something which drives a `Timer` on behalf of the global timer.
+///
+/// By default the global timer is driven by a helper thread spawned on first
+/// use. Environments which can't spawn threads, or which already have an
+/// event loop, can instead install their own driver with `set_global_driver`.
+/// The driver owns a `Timer` and is responsible for polling and advancing it,
+/// as described on `Timer`.
+pub trait TimerDriver: Send + 'static {
+    /// Returns a handle to the `Timer` this driver drives.
+    ///
+    /// Delays created without an explicit handle are bound to this timer.
+    fn handle(&self) -> TimerHandle;
+}
+
+/// Installs `driver` as the driver for the global timer, so that no helper
+/// thread is spawned.
+///
+/// This must be called before any `Delay` is created without an explicit
+/// handle. Returns an error of kind `AlreadyExists` if the global timer is
+/// already running. The driver is dropped by `shutdown_global_timer`, and if
+/// its `Timer` goes away the next `Delay::new` falls back to spawning a helper
+/// thread.
+pub fn set_global_driver(driver: impl TimerDriver) -> io::Result<()> {
+    let mut slot = HELPER.lock().unwrap();
+    if TimerHandle::fallback().is_some() {
+        return Err(already_running());
+    }
+    TimerHandle::install_global(&mut slot, Box::new(driver));
+    Ok(())
+}
+
+fn already_running() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "global timer is already running",
+    )
+}
+
+/// Shuts down the global timer's helper thread, if it's running.
+///
+/// This signals the helper thread to exit and blocks until it has done so, or
+/// drops the driver installed with `set_global_driver`. Any `Delay` still
+/// bound to the global timer becomes inert, and a later call to `Delay::new`
+/// will spin up a fresh helper thread.
+///
+/// Note that a `Delay` created concurrently with a call to this function may
+/// be bound to the timer which is being shut down, and so may also become
+/// inert.
+pub fn shutdown_global_timer() {
+    let helper = HELPER.lock().unwrap().take();
+    drop(helper);
+}
+
+/// Resolves once the global timer has nothing left to do.
+///
+/// That is, once every delay bound to it has fired or been dropped and the
+/// timer has caught up with all updates, which tests can use to assert
+/// quiescence without sleeping for some arbitrary time. Resolves straight
+/// away if the global timer isn't running.
+pub async fn global_idle() {
+    let handle = match TimerHandle::fallback() {
+        Some(handle) => handle,
+        None => return,
+    };
+    IdleWait::new(handle).await
+}
+
+/// Configures the helper thread which drives the global timer.
+///
+/// By default the global timer's helper thread is spawned lazily, with default
+/// settings, by the first `Delay` created without an explicit handle. A
+/// builder can be used to spawn it up front with a custom thread name or stack
+/// size instead, and so must be used before any such `Delay` is created.
+#[derive(Debug, Default)]
+pub struct GlobalTimerBuilder {
+    name: Option<String>,
+    stack_size: Option<usize>,
+    spin_threshold: Duration,
+    granularity: Duration,
+}
+
+impl GlobalTimerBuilder {
+    /// Creates a new builder with the default settings.
+    pub fn new() -> GlobalTimerBuilder {
+        GlobalTimerBuilder::default()
+    }
+
+    /// Sets the name of the helper thread, which defaults to `futures-timer`.
+    pub fn thread_name(mut self, name: &str) -> GlobalTimerBuilder {
+        self.name = Some(name.to_owned());
+        self
+    }
+
+    /// Sets the stack size of the helper thread, in bytes.
+    pub fn stack_size(mut self, size: usize) -> GlobalTimerBuilder {
+        self.stack_size = Some(size);
+        self
+    }
+
+    /// Sets how close the next deadline must be for the helper thread to
+    /// busy-wait for it rather than park.
+    ///
+    /// See `Timer::set_spin_threshold` for details. The default is zero.
+    pub fn spin_threshold(mut self, d: Duration) -> GlobalTimerBuilder {
+        self.spin_threshold = d;
+        self
+    }
+
+    /// Sets the tick the helper thread rounds its wakeups up to.
+    ///
+    /// See `Timer::set_granularity` for details. The default is zero.
+    pub fn granularity(mut self, g: Duration) -> GlobalTimerBuilder {
+        self.granularity = g;
+        self
+    }
+
+    /// Spawns the helper thread and installs it as the global timer.
+    ///
+    /// Returns an error of kind `AlreadyExists` if the global timer is already
+    /// running, or any error encountered spawning the thread. If the global
+    /// timer is later shut down with `shutdown_global_timer`, the thread
+    /// spawned to replace it uses the default settings.
+    pub fn build_global(self) -> io::Result<()> {
+        let mut slot = HELPER.lock().unwrap();
+        if TimerHandle::fallback().is_some() {
+            return Err(already_running());
+        }
+        let mut builder = thread::Builder::new();
+        builder = builder.name(self.name.unwrap_or_else(|| "futures-timer".to_owned()));
+        if let Some(size) = self.stack_size {
+            builder = builder.stack_size(size);
+        }
+        let mut timer = Timer::new();
+        timer.set_spin_threshold(self.spin_threshold);
+        timer.set_granularity(self.granularity);
+        let helper = HelperThread::spawn(builder, timer)?;
+        TimerHandle::install_global(&mut slot, Box::new(helper));
+        Ok(())
+    }
+}
+
 pub struct HelperThread {
     thread: Option<thread::JoinHandle<()>>,
     timer: TimerHandle,
@@ -19,13 +166,15 @@ pub struct HelperThread {
 
 impl HelperThread {
     pub fn new() -> io::Result<HelperThread> {
-        let timer = Timer::new();
+        let builder = thread::Builder::new().name("futures-timer".to_owned());
+        HelperThread::spawn(builder, Timer::new())
+    }
+
+    fn spawn(builder: thread::Builder, timer: Timer) -> io::Result<HelperThread> {
         let timer_handle = timer.handle();
         let done = Arc::new(AtomicBool::new(false));
         let done2 = done.clone();
-        let thread = thread::Builder::new()
-            .name("futures-timer".to_owned())
-            .spawn(move || run(timer, done2))?;
+        let thread = builder.spawn(move || run(timer, done2))?;
 
         Ok(HelperThread {
             thread: Some(thread),
@@ -33,14 +182,12 @@ impl HelperThread {
             timer: timer_handle,
         })
     }
+}
 
-    pub fn handle(&self) -> TimerHandle {
+impl TimerDriver for HelperThread {
+    fn handle(&self) -> TimerHandle {
         self.timer.clone()
     }
-
-    pub fn forget(mut self) {
-        self.thread.take();
-    }
 }
 
 impl Drop for HelperThread {
@@ -62,21 +209,42 @@ fn run(mut timer: Timer, done: Arc<AtomicBool>) {
     while !done.load(Ordering::SeqCst) {
         let _ = Pin::new(&mut timer).poll(&mut cx);
 
-        timer.advance();
-        match timer.next_event() {
+        // Tasks woken by the timers just fired often schedule new ones
+        // straight away, so go round again while timers are firing and only
+        // decide how long to park after a pass which fired nothing.
+        if timer.advance() > 0 {
+            continue;
+        }
+        let parked = Instant::now();
+        let requested = match timer.next_event() {
             // Ok, block for the specified time
             Some(when) => {
-                let now = Instant::now();
-                if now < when {
-                    thread::park_timeout(when - now)
+                if parked < when {
+                    if when - parked <= timer.spin_threshold() {
+                        // Close enough to be worth burning CPU for accuracy.
+                        while Instant::now() < when {
+                            hint::spin_loop();
+                        }
+                    } else {
+                        thread::park_timeout(when - parked)
+                    }
                 } else {
                     // .. continue...
                 }
+                Some(when.saturating_duration_since(parked))
             }
 
             // Just wait for one of our futures to wake up
-            None => thread::park(),
-        }
+            None => {
+                thread::park();
+                None
+            }
+        };
+
+        #[cfg(feature = "metrics")]
+        timer.record_park(requested, parked.elapsed());
+        #[cfg(not(feature = "metrics"))]
+        let _ = requested;
     }
 }
 
@@ -84,7 +252,7 @@ static VTABLE: RawWakerVTable = RawWakerVTable::new(raw_clone, raw_wake, raw_wak
 
 fn raw_clone(ptr: *const ()) -> RawWaker {
     let me = ManuallyDrop::new(unsafe { Arc::from_raw(ptr as *const Thread) });
-    mem::forget(me.clone());
+    mem::forget(Arc::clone(&me));
     RawWaker::new(ptr, &VTABLE)
 }
 
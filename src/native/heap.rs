@@ -1,17 +1,47 @@
-//! A simple binary heap with support for removal of arbitrary elements
+//! A 4-ary heap with support for removal of arbitrary elements.
 //!
 //! This heap is used to manage timer state in the event loop. All timeouts go
 //! into this heap and we also cancel timeouts from this heap. The crucial
 //! feature of this heap over the standard library's `BinaryHeap` is the ability
 //! to remove arbitrary elements. (e.g. when a timer is canceled)
 //!
-//! Note that this heap is not at all optimized right now, it should hopefully
-//! just work.
+//! Each node has four children rather than two, which makes the heap shallower
+//! and keeps siblings next to each other in memory. Timer resets remove and
+//! re-insert entries, so cheaper sifts go a long way under reset churn.
+//!
+//! The heap is exposed as a building block for custom timer structures, along
+//! with `TimerEntry` which orders values by their deadline.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::{Duration, Instant};
+//! use futures_timer::heap::{Heap, TimerEntry};
+//!
+//! let now = Instant::now();
+//! let mut heap = Heap::new();
+//! heap.push(TimerEntry::new(now + Duration::from_secs(2), "later"));
+//! let slot = heap.push(TimerEntry::new(now + Duration::from_secs(3), "cancelled"));
+//! heap.push(TimerEntry::new(now + Duration::from_secs(1), "sooner"));
+//!
+//! assert_eq!(heap.remove(slot).unwrap().value, "cancelled");
+//! assert_eq!(heap.pop().unwrap().value, "sooner");
+//! assert_eq!(heap.pop().unwrap().value, "later");
+//! assert!(heap.pop().is_none());
+//! ```
 
+use std::cmp::Ordering;
+use std::fmt;
 use std::mem;
+use std::time::Instant;
+
+/// Number of children of each node in the heap.
+const ARITY: usize = 4;
 
+/// A min-heap whose elements can be removed through the `Slot` returned when
+/// they were pushed.
 pub struct Heap<T> {
-    // Binary heap of items, plus the slab index indicating what position in the
+    // 4-ary heap of items, plus the slab index indicating what position in the
     // list they're in.
     items: Vec<(T, usize)>,
 
@@ -19,23 +49,71 @@ pub struct Heap<T> {
     // in the array the item appears at.
     index: Vec<SlabSlot<usize>>,
     next_index: usize,
+
+    // Handed out to each pushed element, and recorded in its slab entry and
+    // `Slot`, so that a slot whose entry has since been reused is detected.
+    next_gen: usize,
 }
 
 enum SlabSlot<T> {
     Empty { next: usize },
-    Full { value: T },
+    Full { value: T, gen: usize },
 }
 
+/// A token identifying an element pushed onto a `Heap`, used to remove it.
+#[derive(Debug)]
 pub struct Slot {
     idx: usize,
+    gen: usize,
+}
+
+/// A value paired with the instant it's due, ordered by that instant.
+///
+/// Entries with equal deadlines compare equal regardless of their values.
+#[derive(Debug)]
+pub struct TimerEntry<T> {
+    /// The instant this entry is due.
+    pub at: Instant,
+
+    /// The value associated with this entry.
+    pub value: T,
+}
+
+impl<T> TimerEntry<T> {
+    /// Creates a new entry for `value` which is due at `at`.
+    pub fn new(at: Instant, value: T) -> TimerEntry<T> {
+        TimerEntry { at, value }
+    }
+}
+
+impl<T> PartialEq for TimerEntry<T> {
+    fn eq(&self, other: &TimerEntry<T>) -> bool {
+        self.at == other.at
+    }
+}
+
+impl<T> Eq for TimerEntry<T> {}
+
+impl<T> PartialOrd for TimerEntry<T> {
+    fn partial_cmp(&self, other: &TimerEntry<T>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for TimerEntry<T> {
+    fn cmp(&self, other: &TimerEntry<T>) -> Ordering {
+        self.at.cmp(&other.at)
+    }
 }
 
 impl<T: Ord> Heap<T> {
+    /// Creates a new, empty heap.
     pub fn new() -> Heap<T> {
         Heap {
             items: Vec::new(),
             index: Vec::new(),
             next_index: 0,
+            next_gen: 0,
         }
     }
 
@@ -43,11 +121,13 @@ impl<T: Ord> Heap<T> {
     /// where it was pushed on to.
     ///
     /// The slot can later get passed to `remove` to remove the element from the
-    /// heap, but only if the element was previously not removed from the heap.
+    /// heap, if it's still there.
     pub fn push(&mut self, t: T) -> Slot {
         self.assert_consistent();
         let len = self.items.len();
-        let slot = SlabSlot::Full { value: len };
+        let gen = self.next_gen;
+        self.next_gen = self.next_gen.wrapping_add(1);
+        let slot = SlabSlot::Full { value: len, gen };
         let slot_idx = if self.next_index == self.index.len() {
             self.next_index += 1;
             self.index.push(slot);
@@ -61,33 +141,53 @@ impl<T: Ord> Heap<T> {
         self.items.push((t, slot_idx));
         self.percolate_up(len);
         self.assert_consistent();
-        Slot { idx: slot_idx }
+        Slot { idx: slot_idx, gen }
     }
 
+    /// Returns the number of elements in the heap.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns `true` if the heap contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the smallest element of the heap, if any.
     pub fn peek(&self) -> Option<&T> {
         self.assert_consistent();
         self.items.first().map(|i| &i.0)
     }
 
+    /// Removes and returns the smallest element of the heap, if any.
     pub fn pop(&mut self) -> Option<T> {
         self.assert_consistent();
         if self.items.is_empty() {
             return None;
         }
-        let slot = Slot {
-            idx: self.items[0].1,
+        let idx = self.items[0].1;
+        let gen = match self.index[idx] {
+            SlabSlot::Full { gen, .. } => gen,
+            SlabSlot::Empty { .. } => panic!(),
         };
-        Some(self.remove(slot))
+        self.remove(Slot { idx, gen })
     }
 
-    pub fn remove(&mut self, slot: Slot) -> T {
+    /// Removes the element identified by `slot`, returning it.
+    ///
+    /// Returns `None` if the element has already left the heap through
+    /// `pop`, even if its slot has since been reused for another element.
+    /// Slots from a different heap aren't detected, and may remove an
+    /// unrelated element or panic.
+    pub fn remove(&mut self, slot: Slot) -> Option<T> {
         self.assert_consistent();
-        let empty = SlabSlot::Empty {
-            next: self.next_index,
+        let idx = match self.index.get(slot.idx) {
+            Some(&SlabSlot::Full { value, gen }) if gen == slot.gen => value,
+            _ => return None,
         };
-        let idx = match mem::replace(&mut self.index[slot.idx], empty) {
-            SlabSlot::Full { value } => value,
-            SlabSlot::Empty { .. } => panic!(),
+        self.index[slot.idx] = SlabSlot::Empty {
+            next: self.next_index,
         };
         self.next_index = slot.idx;
         let (item, slot_idx) = self.items.swap_remove(idx);
@@ -101,12 +201,12 @@ impl<T: Ord> Heap<T> {
             }
         }
         self.assert_consistent();
-        item
+        Some(item)
     }
 
     fn percolate_up(&mut self, mut idx: usize) -> usize {
         while idx > 0 {
-            let parent = (idx - 1) / 2;
+            let parent = (idx - 1) / ARITY;
             if self.items[idx].0 >= self.items[parent].0 {
                 break;
             }
@@ -121,41 +221,26 @@ impl<T: Ord> Heap<T> {
 
     fn percolate_down(&mut self, mut idx: usize) -> usize {
         loop {
-            let left = 2 * idx + 1;
-            let right = 2 * idx + 2;
-
-            let mut swap_left = true;
-            match (self.items.get(left), self.items.get(right)) {
-                (Some(left), None) => {
-                    if left.0 >= self.items[idx].0 {
-                        break;
-                    }
-                }
-                (Some(left), Some(right)) => {
-                    if left.0 < self.items[idx].0 {
-                        if right.0 < left.0 {
-                            swap_left = false;
-                        }
-                    } else if right.0 < self.items[idx].0 {
-                        swap_left = false;
-                    } else {
-                        break;
-                    }
+            let first = ARITY * idx + 1;
+            if first >= self.items.len() {
+                break;
+            }
+            let last = (first + ARITY).min(self.items.len());
+            let mut child = first;
+            for i in first + 1..last {
+                if self.items[i].0 < self.items[child].0 {
+                    child = i;
                 }
-
-                (None, None) => break,
-                (None, Some(_right)) => panic!("not possible"),
+            }
+            if self.items[child].0 >= self.items[idx].0 {
+                break;
             }
 
-            let (a, b) = if swap_left {
-                self.items.split_at_mut(left)
-            } else {
-                self.items.split_at_mut(right)
-            };
+            let (a, b) = self.items.split_at_mut(child);
             mem::swap(&mut a[idx], &mut b[0]);
             set_index(&mut self.index, a[idx].1, idx);
-            set_index(&mut self.index, b[0].1, a.len());
-            idx = a.len();
+            set_index(&mut self.index, b[0].1, child);
+            idx = child;
         }
         idx
     }
@@ -180,7 +265,7 @@ impl<T: Ord> Heap<T> {
 
         for (i, &(_, j)) in self.items.iter().enumerate() {
             let index = match self.index[j] {
-                SlabSlot::Full { value } => value,
+                SlabSlot::Full { value, .. } => value,
                 SlabSlot::Empty { .. } => panic!(),
             };
             if index != i {
@@ -193,21 +278,36 @@ impl<T: Ord> Heap<T> {
 
         for (i, (item, _)) in self.items.iter().enumerate() {
             if i > 0 {
-                assert!(*item >= self.items[(i - 1) / 2].0, "bad at index: {}", i);
-            }
-            if let Some(left) = self.items.get(2 * i + 1) {
-                assert!(*item <= left.0, "bad left at index: {}", i);
+                assert!(
+                    *item >= self.items[(i - 1) / ARITY].0,
+                    "bad at index: {}",
+                    i
+                );
             }
-            if let Some(right) = self.items.get(2 * i + 2) {
-                assert!(*item <= right.0, "bad right at index: {}", i);
+            for child in self.items.iter().skip(ARITY * i + 1).take(ARITY) {
+                assert!(*item <= child.0, "bad child at index: {}", i);
             }
         }
     }
 }
 
-fn set_index<T>(slab: &mut Vec<SlabSlot<T>>, slab_slot: usize, val: T) {
+impl<T: Ord> Default for Heap<T> {
+    fn default() -> Heap<T> {
+        Heap::new()
+    }
+}
+
+impl<T> fmt::Debug for Heap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Heap")
+            .field("len", &self.items.len())
+            .finish()
+    }
+}
+
+fn set_index<T>(slab: &mut [SlabSlot<T>], slab_slot: usize, val: T) {
     match slab[slab_slot] {
-        SlabSlot::Full { ref mut value } => *value = val,
+        SlabSlot::Full { ref mut value, .. } => *value = val,
         SlabSlot::Empty { .. } => panic!(),
     }
 }
@@ -261,7 +361,7 @@ mod tests {
         let two = h.push(2);
         h.push(1);
         assert_eq!(h.pop(), Some(1));
-        assert_eq!(h.remove(two), 2);
+        assert_eq!(h.remove(two), Some(2));
         h.push(1);
         assert_eq!(h.pop(), Some(1));
         assert_eq!(h.pop(), Some(3));
@@ -336,6 +436,29 @@ mod tests {
         check_to_vec(vec![5, 4, 3, 2, 1, 5, 4, 3, 2, 1, 5, 4, 3, 2, 1]);
     }
 
+    #[test]
+    fn remove_many() {
+        let mut heap = Heap::new();
+        let mut slots = Vec::new();
+        for i in 0..1000 {
+            slots.push(Some(heap.push((i * 7919) % 1000)));
+        }
+        let mut removed = Vec::new();
+        for i in (0..1000).step_by(3) {
+            removed.push(heap.remove(slots[i].take().unwrap()).unwrap());
+        }
+        let mut expected = (0..1000)
+            .map(|i| (i * 7919) % 1000)
+            .filter(|v| !removed.contains(v))
+            .collect::<Vec<_>>();
+        expected.sort();
+        let mut v = Vec::new();
+        while let Some(i) = heap.pop() {
+            v.push(i);
+        }
+        assert_eq!(v, expected);
+    }
+
     #[test]
     fn test_empty_pop() {
         let mut heap = Heap::<i32>::new();
@@ -6,15 +6,19 @@ use super::{Node, ScheduledTimer};
 
 /// Entries in the timer heap, sorted by the instant they're firing at and then
 /// also containing some payload data.
+///
+/// Entries firing at the same instant are ordered by `seq`, so they fire in
+/// the order they were scheduled.
 pub(crate) struct HeapTimer {
     pub(crate) at: Instant,
+    pub(crate) seq: usize,
     pub(crate) gen: usize,
     pub(crate) node: Arc<Node<ScheduledTimer>>,
 }
 
 impl PartialEq for HeapTimer {
     fn eq(&self, other: &HeapTimer) -> bool {
-        self.at == other.at
+        self.at == other.at && self.seq == other.seq
     }
 }
 
@@ -28,6 +32,6 @@ impl PartialOrd for HeapTimer {
 
 impl Ord for HeapTimer {
     fn cmp(&self, other: &HeapTimer) -> Ordering {
-        self.at.cmp(&other.at)
+        (self.at, self.seq).cmp(&(other.at, other.seq))
     }
 }
@@ -0,0 +1,86 @@
+//! A compact histogram of durations, for `Timer::lateness_histogram`.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::time::Duration;
+
+/// Number of buckets: one for zero and one per power of two of nanoseconds
+/// representable in a `u64`.
+const BUCKETS: usize = 65;
+
+/// A histogram of durations with power-of-two buckets.
+///
+/// Bucket `i` holds durations of fewer than `2^i` nanoseconds, but at least
+/// `2^(i - 1)`, so quantiles are accurate to within a factor of two across
+/// the whole range from nanoseconds to centuries, in constant space.
+#[derive(Clone)]
+pub struct Histogram {
+    counts: [u64; BUCKETS],
+    count: u64,
+    max: Duration,
+}
+
+impl Histogram {
+    pub(crate) fn new() -> Histogram {
+        Histogram {
+            counts: [0; BUCKETS],
+            count: 0,
+            max: Duration::from_secs(0),
+        }
+    }
+
+    pub(crate) fn record(&mut self, d: Duration) {
+        let nanos = u64::try_from(d.as_nanos()).unwrap_or(u64::MAX);
+        self.counts[(u64::BITS - nanos.leading_zeros()) as usize] += 1;
+        self.count += 1;
+        self.max = self.max.max(d);
+    }
+
+    /// Returns the number of samples recorded.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the largest sample recorded, or zero if there are none.
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// Returns an upper bound on the sample at quantile `q`, e.g. `0.99` for
+    /// the 99th percentile, or `None` if no samples have been recorded.
+    ///
+    /// The bound is the top of the bucket the sample fell in, capped at the
+    /// largest sample, so it's at most twice the true value. `q` is clamped
+    /// to `[0, 1]`.
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let q = if q.is_nan() { 0.0 } else { q.clamp(0.0, 1.0) };
+        let rank = ((q * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, &n) in self.counts.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                let upper = match i {
+                    0 => Duration::from_secs(0),
+                    // `2^i - 1` nanoseconds, without overflowing for i = 64
+                    i => Duration::from_nanos(u64::MAX >> (64 - i)),
+                };
+                return Some(upper.min(self.max));
+            }
+        }
+        Some(self.max)
+    }
+}
+
+impl fmt::Debug for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Histogram")
+            .field("count", &self.count)
+            .field("p50", &self.quantile(0.5))
+            .field("p99", &self.quantile(0.99))
+            .field("max", &self.max)
+            .finish()
+    }
+}
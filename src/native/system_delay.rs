@@ -0,0 +1,94 @@
+//! A delay until a wall-clock time which follows adjustments to the clock.
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+
+use super::Delay;
+
+/// A delay which fires once the system clock reaches a given time.
+///
+/// `Delay::until_system_time` converts its target to an `Instant` once, so if
+/// the wall clock is later adjusted, say by an NTP correction, the delay fires
+/// at the wrong wall-clock time. A `SystemDelay` instead re-reads the clock
+/// at least once per check interval, one second by default, and whenever its
+/// underlying `Delay` fires, re-arming it for whatever time is left. A clock
+/// stepping backwards just means it re-arms, and one stepping forwards past
+/// the target is noticed within a check interval.
+///
+/// The clock is `SystemTime::now` unless another is given to `with_clock`,
+/// which is mostly useful for testing.
+pub struct SystemDelay<C = fn() -> SystemTime> {
+    target: SystemTime,
+    clock: C,
+    check_interval: Duration,
+    delay: Delay,
+}
+
+const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+impl SystemDelay {
+    /// Creates a delay which fires once `SystemTime::now()` reaches `target`.
+    pub fn new(target: SystemTime) -> SystemDelay {
+        SystemDelay::with_clock(target, SystemTime::now as fn() -> SystemTime)
+    }
+}
+
+impl<C: Fn() -> SystemTime> SystemDelay<C> {
+    /// Creates a delay which fires once `clock()` reaches `target`.
+    pub fn with_clock(target: SystemTime, clock: C) -> SystemDelay<C> {
+        let remaining = target.duration_since(clock()).unwrap_or_default();
+        SystemDelay {
+            target,
+            clock,
+            check_interval: DEFAULT_CHECK_INTERVAL,
+            delay: Delay::new(remaining.min(DEFAULT_CHECK_INTERVAL)),
+        }
+    }
+
+    /// Sets how long the clock may go unchecked.
+    ///
+    /// A shorter interval notices the clock jumping forward sooner, at the
+    /// cost of waking up more often.
+    pub fn set_check_interval(&mut self, interval: Duration) {
+        self.check_interval = interval;
+        if self.delay.saturating_remaining() > interval {
+            self.delay.reset(interval);
+        }
+    }
+
+    /// Returns the wall-clock time this delay fires at.
+    pub fn target(&self) -> SystemTime {
+        self.target
+    }
+}
+
+impl<C: Fn() -> SystemTime + Unpin> Future for SystemDelay<C> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        loop {
+            if Pin::new(&mut this.delay).poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            let remaining = match this.target.duration_since((this.clock)()) {
+                Ok(remaining) if remaining > Duration::from_secs(0) => remaining,
+                _ => return Poll::Ready(()),
+            };
+            this.delay.reset(remaining.min(this.check_interval));
+        }
+    }
+}
+
+impl<C> fmt::Debug for SystemDelay<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SystemDelay")
+            .field("target", &self.target)
+            .field("check_interval", &self.check_interval)
+            .field("delay", &self.delay)
+            .finish()
+    }
+}
@@ -0,0 +1,55 @@
+//! A `Delay` which re-arms itself each time it fires.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use super::Delay;
+
+/// A delay which, each time it's observed to have fired, re-arms itself to
+/// fire again one period later.
+///
+/// This is created by `Delay::into_ticker`. Unlike most futures a `Ticker`
+/// may keep being polled after it resolves, yielding `Ready(())` once per
+/// period, which makes it easy to embed in hand-written `poll` functions.
+/// Each period is measured from the poll which observed the previous tick, so
+/// time spent between ticks pushes later ticks back.
+#[derive(Debug)]
+pub struct Ticker {
+    delay: Delay,
+    period: Duration,
+}
+
+impl Delay {
+    /// Converts this delay into a `Ticker` which fires when this delay does,
+    /// and then every `period` after each poll that observes it firing.
+    pub fn into_ticker(self, period: Duration) -> Ticker {
+        Ticker {
+            delay: self,
+            period,
+        }
+    }
+}
+
+impl Ticker {
+    /// Returns the period between ticks.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+}
+
+impl Future for Ticker {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        match Pin::new(&mut self.delay).poll(cx) {
+            Poll::Ready(()) => {
+                let period = self.period;
+                self.delay.reset(period);
+                Poll::Ready(())
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
@@ -1,39 +1,95 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
 use std::fmt;
 use std::pin::Pin;
+use std::sync::atomic::AtomicPtr;
 use std::sync::atomic::Ordering::SeqCst;
-use std::sync::atomic::{AtomicPtr, AtomicUsize};
-use std::sync::{Arc, Mutex, Weak};
-use std::task::{Context, Poll};
-use std::time::Instant;
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
 
 use std::future::Future;
 
+// As in `arc_list`, the state shared between the timer and its delays uses
+// loom's primitives under `--cfg futures_timer_loom`.
+#[cfg(futures_timer_loom)]
+use loom::sync::{
+    atomic::{AtomicBool, AtomicUsize},
+    Mutex,
+};
+#[cfg(not(futures_timer_loom))]
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize},
+    Mutex,
+};
+
+use super::global::{self, TimerDriver};
 use super::AtomicWaker;
-use super::{global, ArcList, Heap, HeapTimer, Node, Slot};
+#[cfg(feature = "metrics")]
+use super::Histogram;
+use super::{ArcList, Heap, HeapTimer, Node, Slot};
 
 /// A "timer heap" used to power separately owned instances of `Delay`.
 ///
 /// This timer is implemented as a priority queued-based heap. Each `Timer`
 /// contains a few primary methods which which to drive it:
 ///
-/// * `next_wake` indicates how long the ambient system needs to sleep until it
+/// * `next_event` indicates how long the ambient system needs to sleep until it
 ///   invokes further processing on a `Timer`
 /// * `advance_to` is what actually fires timers on the `Timer`, and should be
 ///   called essentially every iteration of the event loop, or when the time
-///   specified by `next_wake` has elapsed.
+///   specified by `next_event` has elapsed.
 /// * The `Future` implementation for `Timer` is used to process incoming timer
 ///   updates and requests. This is used to schedule new timeouts, update
 ///   existing ones, or delete existing timeouts. The `Future` implementation
 ///   will never resolve, but it'll schedule notifications of when to wake up
 ///   and process more messages.
 ///
+/// To drive a `Timer` by hand, each iteration of the event loop should first
+/// poll the `Timer` so that it sees any delays created or reset through its
+/// `handle`, then call `advance` to fire everything that's due, and finally
+/// sleep until `next_event` or until the `Timer`'s task is woken, whichever
+/// comes first. Polling first matters: `next_event` and `advance` only know
+/// about updates the `Timer` has already processed.
+///
 /// Note that if you're using this crate you probably don't need to use a
 /// `Timer` as there is a global one already available for you run on a helper
-/// thread. If this isn't desirable, though, then the
-/// `TimerHandle::set_fallback` method can be used instead!
+/// thread.
 pub struct Timer {
     inner: Arc<Inner>,
     timer_heap: Heap<HeapTimer>,
+    coalesce_window: Duration,
+    spin_threshold: Duration,
+    granularity: Duration,
+    origin: Instant,
+    #[cfg(feature = "metrics")]
+    on_wakeup: Option<Box<dyn FnMut(WakeupStats) + Send>>,
+    #[cfg(feature = "metrics")]
+    last_park: (Option<Duration>, Option<Duration>),
+    #[cfg(feature = "metrics")]
+    lateness: Histogram,
+}
+
+/// Statistics about a single pass of `Timer::advance_to`, passed to the
+/// callback registered with `Timer::on_wakeup`.
+#[cfg(feature = "metrics")]
+#[derive(Clone, Copy, Debug)]
+pub struct WakeupStats {
+    /// The number of timers fired during this pass.
+    pub fired: usize,
+
+    /// How long the helper thread asked to park for before this pass.
+    ///
+    /// This is `None` if it parked without a timeout, if it went straight
+    /// into this pass because the last one fired timers, or if the timer
+    /// isn't driven by the helper thread.
+    pub requested_park: Option<Duration>,
+
+    /// How long the helper thread actually parked for before this pass.
+    ///
+    /// This is `None` if it didn't park before this pass, or if the timer
+    /// isn't driven by the helper thread.
+    pub actual_park: Option<Duration>,
 }
 
 /// A handle to a `Timer` which is used to create instances of a `Delay`.
@@ -48,12 +104,39 @@ pub(crate) struct Inner {
 
     /// The blocked `Timer` task to receive notifications to the `list` above.
     pub(crate) waker: AtomicWaker,
+
+    /// Number of live `Delay` instances bound to this timer.
+    pub(crate) live: AtomicUsize,
+
+    /// Number of those delays which haven't fired yet. Whoever sets a
+    /// timer's fired bit takes it off this count, and a reset which clears
+    /// the bit puts it back.
+    pub(crate) scheduled: AtomicUsize,
+
+    /// Source of the sequence numbers handed out to scheduled timers.
+    pub(crate) next_seq: AtomicUsize,
+
+    /// Whether the heap was empty at the end of the last pass over it, and
+    /// the tasks waiting to hear about that, for `global_idle`.
+    idle: AtomicBool,
+    idle_waiters: Mutex<IdleWaiters>,
+}
+
+/// The wakers of every pending `IdleWait`, each keyed by an id of its own so
+/// it can take its entry back out when it's dropped.
+#[derive(Default)]
+struct IdleWaiters {
+    next_id: usize,
+    wakers: BTreeMap<usize, Waker>,
 }
 
 /// Shared state between the `Timer` and a `Delay`.
 pub(crate) struct ScheduledTimer {
     pub(crate) waker: AtomicWaker,
 
+    // Woken whenever the timer is reset, for `ResetNotify`.
+    pub(crate) reset_waker: AtomicWaker,
+
     // The lowest bit here is whether the timer has fired or not, the second
     // lowest bit is whether the timer has been invalidated, and all the other
     // bits are the "generation" of the timer which is reset during the `reset`
@@ -63,11 +146,33 @@ pub(crate) struct ScheduledTimer {
     pub(crate) inner: Weak<Inner>,
     pub(crate) at: Mutex<Option<Instant>>,
 
+    // Assigned each time the timer is scheduled or reset, so that timers with
+    // the same deadline fire in the order they were scheduled.
+    pub(crate) seq: AtomicUsize,
+
+    // Set once the owning `Delay` is dropped, for `WeakDelay`. The
+    // timer may keep the node alive for a while after that.
+    pub(crate) dropped: AtomicBool,
+
     // TODO: this is only accessed by the timer thread, should have a more
     // lightweight protection than a `Mutex`
     pub(crate) slot: Mutex<Option<Slot>>,
 }
 
+impl ScheduledTimer {
+    /// Sets the fired bit, returning whether it was clear before, in which
+    /// case the timer is also taken off its `Timer`'s scheduled count.
+    pub(crate) fn mark_fired(&self) -> bool {
+        if self.state.fetch_or(0b01, SeqCst) & 0b01 != 0 {
+            return false;
+        }
+        if let Some(inner) = self.inner.upgrade() {
+            inner.scheduled.fetch_sub(1, SeqCst);
+        }
+        true
+    }
+}
+
 impl Timer {
     /// Creates a new timer heap ready to create new timers.
     pub fn new() -> Timer {
@@ -75,8 +180,23 @@ impl Timer {
             inner: Arc::new(Inner {
                 list: ArcList::new(),
                 waker: AtomicWaker::new(),
+                live: AtomicUsize::new(0),
+                scheduled: AtomicUsize::new(0),
+                next_seq: AtomicUsize::new(0),
+                idle: AtomicBool::new(true),
+                idle_waiters: Mutex::new(IdleWaiters::default()),
             }),
             timer_heap: Heap::new(),
+            coalesce_window: Duration::from_secs(0),
+            spin_threshold: Duration::from_secs(0),
+            granularity: Duration::from_secs(0),
+            origin: Instant::now(),
+            #[cfg(feature = "metrics")]
+            on_wakeup: None,
+            #[cfg(feature = "metrics")]
+            last_park: (None, None),
+            #[cfg(feature = "metrics")]
+            lateness: Histogram::new(),
         }
     }
 
@@ -87,20 +207,130 @@ impl Timer {
         }
     }
 
+    /// Returns the number of timers currently scheduled on this timer.
+    ///
+    /// This counts every `Delay` bound to this timer which hasn't fired yet,
+    /// including those still waiting to be picked up from the update list
+    /// and those which are paused. Delays which have fired or been cancelled
+    /// drop out of the count even before they're dropped, and come back if
+    /// they're reset.
+    pub fn len(&self) -> usize {
+        self.inner.scheduled.load(SeqCst)
+    }
+
+    /// Returns `true` if no timers are scheduled on this timer, that is if
+    /// every delay bound to it has fired or been cancelled. See `len` for
+    /// what is counted.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Configures a window within which nearly-simultaneous timers are fired
+    /// together.
+    ///
+    /// Whenever `advance_to` fires a timer, any other timer due within `slack`
+    /// of it is fired at the same time, even if its deadline hasn't been
+    /// reached yet. This trades precision for fewer wakeups: timers may fire
+    /// up to `slack` early. The default window is zero.
+    pub fn set_coalesce_window(&mut self, slack: Duration) {
+        self.coalesce_window = slack;
+    }
+
+    /// Configures how close the next deadline must be for the thread driving
+    /// this timer to busy-wait for it rather than park.
+    ///
+    /// Parking with a timeout can overshoot by a millisecond or more on some
+    /// platforms, so spinning until short deadlines improves accuracy at the
+    /// cost of burning CPU. The global timer's helper thread honors this
+    /// setting, which can be configured through `GlobalTimerBuilder`. The
+    /// default threshold is zero, meaning the thread never spins.
+    pub fn set_spin_threshold(&mut self, d: Duration) {
+        self.spin_threshold = d;
+    }
+
+    /// Returns the threshold configured with `set_spin_threshold`.
+    pub fn spin_threshold(&self) -> Duration {
+        self.spin_threshold
+    }
+
+    /// Configures a tick which `next_event` rounds deadlines up to.
+    ///
+    /// With a non-zero granularity the thread driving this timer only wakes
+    /// on multiples of `g`, counted from when the timer was created, so on
+    /// battery-powered devices the CPU can stay asleep for longer. The price
+    /// is precision: a delay may fire up to `g` late. Unlike
+    /// `set_coalesce_window` this never fires anything early; it only changes
+    /// how long the driver parks for. The default granularity is zero,
+    /// meaning deadlines aren't rounded.
+    pub fn set_granularity(&mut self, g: Duration) {
+        self.granularity = g;
+    }
+
+    /// Returns the granularity configured with `set_granularity`.
+    pub fn granularity(&self) -> Duration {
+        self.granularity
+    }
+
+    /// Registers a callback invoked with statistics after every call to
+    /// `advance_to`.
+    ///
+    /// This replaces any previously registered callback.
+    #[cfg(feature = "metrics")]
+    pub fn on_wakeup(&mut self, f: impl FnMut(WakeupStats) + Send + 'static) {
+        self.on_wakeup = Some(Box::new(f));
+    }
+
+    /// Returns a histogram of how late each timer fired, relative to its
+    /// deadline.
+    ///
+    /// Each timer fired by `advance_to` records the time between its deadline
+    /// and the instant passed to `advance_to`, which for a timer driven in
+    /// real time, like the global one, is when the driver woke up to fire it.
+    /// Timers fired early by `set_coalesce_window` record zero.
+    #[cfg(feature = "metrics")]
+    pub fn lateness_histogram(&self) -> Histogram {
+        self.lateness.clone()
+    }
+
+    /// Records how long the thread driving this timer parked for, to be
+    /// reported on the next call to `advance_to`.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn record_park(&mut self, requested: Option<Duration>, actual: Duration) {
+        self.last_park = (requested, Some(actual));
+    }
+
     /// Returns the time at which this timer next needs to be invoked with
     /// `advance_to`.
     ///
     /// Event loops or threads typically want to sleep until the specified
     /// instant.
+    ///
+    /// The instant is rounded up according to `set_granularity`.
     pub fn next_event(&self) -> Option<Instant> {
-        self.timer_heap.peek().map(|t| t.at)
+        self.timer_heap.peek().map(|t| self.round_up(t.at))
+    }
+
+    fn round_up(&self, at: Instant) -> Instant {
+        let g = self.granularity.as_nanos();
+        if g == 0 || at <= self.origin {
+            return at;
+        }
+        match (at - self.origin).as_nanos() % g {
+            0 => at,
+            // A huge granularity, or an instant near the limit of what
+            // `Instant` can represent, can round past it; leave those alone.
+            rem => u64::try_from(g - rem)
+                .ok()
+                .and_then(|n| at.checked_add(Duration::from_nanos(n)))
+                .unwrap_or(at),
+        }
     }
 
     /// Proces any timers which are supposed to fire at or before the current
     /// instant.
     ///
     /// This method is equivalent to `self.advance_to(Instant::now())`.
-    pub fn advance(&mut self) {
+    pub fn advance(&mut self) -> usize {
         self.advance_to(Instant::now())
     }
 
@@ -108,10 +338,23 @@ impl Timer {
     ///
     /// This method should be called on `Timer` periodically to advance the
     /// internal state and process any pending timers which need to fire.
-    pub fn advance_to(&mut self, now: Instant) {
+    ///
+    /// Timers are fired, and their tasks woken, strictly in deadline order,
+    /// with timers sharing a deadline fired in the order they were scheduled
+    /// or last reset. Every timer fired by one call is ready from then on,
+    /// though, so when a single task waits on several of them, which it sees
+    /// first is down to the order it polls them in, e.g. the arm order of a
+    /// biased `select!`.
+    ///
+    /// Returns the number of timers fired, so that a driver which fired
+    /// nothing knows no task was woken.
+    pub fn advance_to(&mut self, now: Instant) -> usize {
+        let mut cutoff = now;
+        let mut coalesced = false;
+        let mut fired = 0;
         loop {
             match self.timer_heap.peek() {
-                Some(head) if head.at <= now => {}
+                Some(head) if head.at <= cutoff => {}
                 Some(_) => break,
                 None => break,
             };
@@ -119,32 +362,121 @@ impl Timer {
             // Flag the timer as fired and then notify its task, if any, that's
             // blocked.
             let heap_timer = self.timer_heap.pop().unwrap();
-            *heap_timer.node.slot.lock().unwrap() = None;
-            let bits = heap_timer.gen << 2;
-            match heap_timer
-                .node
-                .state
-                .compare_exchange(bits, bits | 0b01, SeqCst, SeqCst)
-            {
-                Ok(_) => heap_timer.node.waker.wake(),
-                Err(_b) => {}
+
+            // Anything due within the coalescing window of the earliest timer
+            // we fire gets fired along with it.
+            if !coalesced {
+                coalesced = true;
+                if let Some(at) = heap_timer.at.checked_add(self.coalesce_window) {
+                    cutoff = cutoff.max(at);
+                }
+            }
+
+            #[cfg(feature = "metrics")]
+            let at = heap_timer.at;
+            if fire(&self.inner, heap_timer) {
+                fired += 1;
+                #[cfg(feature = "metrics")]
+                self.lateness.record(now.saturating_duration_since(at));
+            }
+        }
+
+        self.update_idle();
+
+        #[cfg(feature = "metrics")]
+        {
+            let (requested_park, actual_park) = self.last_park;
+            self.last_park = (None, None);
+            if let Some(f) = &mut self.on_wakeup {
+                f(WakeupStats {
+                    fired,
+                    requested_park,
+                    actual_park,
+                });
+            }
+        }
+        fired
+    }
+
+    /// Fires every timer registered with this timer, regardless of deadline.
+    ///
+    /// Pending updates are processed first, so delays which were just created
+    /// or reset are included, and the heap is left empty. This is a
+    /// deterministic way to drain a manually driven timer, say at the end of
+    /// a test, without advancing to some far-off instant. Returns the number
+    /// of timers fired.
+    pub fn fire_all(&mut self) -> usize {
+        self.process_updates();
+        let mut fired = 0;
+        while let Some(heap_timer) = self.timer_heap.pop() {
+            if fire(&self.inner, heap_timer) {
+                fired += 1;
+            }
+        }
+        self.update_idle();
+        fired
+    }
+
+    /// Shuts this timer down, returning a future which resolves once every
+    /// delay bound to it has been dropped.
+    ///
+    /// Shutting down does everything dropping the timer does, straight away:
+    /// every scheduled delay is invalidated and its task woken, and delays
+    /// created or reset from then on are inert. Dropping the timer doesn't
+    /// wait for anything though, whereas the returned future keeps the
+    /// timer's shared state alive until the last delay using it lets go, so
+    /// tearing down a runtime can be sequenced after all of its timers.
+    pub fn into_shutdown(self) -> ShutdownGuard {
+        let inner = self.inner.clone();
+        drop(self);
+        ShutdownGuard { inner }
+    }
+
+    /// Applies the updates delays have pushed since the last call.
+    fn process_updates(&mut self) {
+        // Not idle while updates are in flight between the list and the
+        // heap; `update_idle` settles it again afterwards.
+        self.inner.idle.store(false, SeqCst);
+        let mut list = self.inner.list.take();
+        while let Some(node) = list.pop() {
+            // Load the generation before the deadline; `Delay::reset_at`
+            // stores them in the opposite order, so whenever we see a new
+            // generation we're guaranteed to also see its deadline.
+            let gen = node.state.load(SeqCst) >> 2;
+            let at = *node.at.lock().unwrap();
+            match at {
+                Some(at) => self.update_or_add(at, gen, node),
+                None => self.remove(node),
+            }
+        }
+    }
+
+    /// Records whether the heap is empty at the end of a pass, waking anyone
+    /// waiting for it to be.
+    fn update_idle(&self) {
+        let idle = self.timer_heap.is_empty();
+        self.inner.idle.store(idle, SeqCst);
+        if idle {
+            // Each waiter removes its own entry once it's done waiting.
+            for waker in self.inner.idle_waiters.lock().unwrap().wakers.values() {
+                waker.wake_by_ref();
             }
         }
     }
 
     /// Either updates the timer at slot `idx` to fire at `at`, or adds a new
     /// timer at `idx` and sets it to fire at `at`.
-    fn update_or_add(&mut self, at: Instant, node: Arc<Node<ScheduledTimer>>) {
+    fn update_or_add(&mut self, at: Instant, gen: usize, node: Arc<Node<ScheduledTimer>>) {
         // TODO: avoid remove + push and instead just do one sift of the heap?
         // In theory we could update it in place and then do the percolation
         // as necessary
-        let gen = node.state.load(SeqCst) >> 2;
         let mut slot = node.slot.lock().unwrap();
         if let Some(heap_slot) = slot.take() {
             self.timer_heap.remove(heap_slot);
         }
         *slot = Some(self.timer_heap.push(HeapTimer {
             at,
+            seq: node.seq.load(SeqCst),
             gen,
             node: node.clone(),
         }));
@@ -167,19 +499,33 @@ impl Timer {
     }
 }
 
+/// Marks a timer popped off the heap as fired and wakes its task, returning
+/// whether it fired. Timers which have since been reset to a new generation
+/// are skipped.
+fn fire(inner: &Inner, heap_timer: HeapTimer) -> bool {
+    *heap_timer.node.slot.lock().unwrap() = None;
+    let bits = heap_timer.gen << 2;
+    match heap_timer
+        .node
+        .state
+        .compare_exchange(bits, bits | 0b01, SeqCst, SeqCst)
+    {
+        Ok(_) => {
+            inner.scheduled.fetch_sub(1, SeqCst);
+            heap_timer.node.waker.wake();
+            true
+        }
+        Err(_b) => false,
+    }
+}
+
 impl Future for Timer {
     type Output = ();
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         Pin::new(&mut self.inner).waker.register(cx.waker());
-        let mut list = self.inner.list.take();
-        while let Some(node) = list.pop() {
-            let at = *node.at.lock().unwrap();
-            match at {
-                Some(at) => self.update_or_add(at, node),
-                None => self.remove(node),
-            }
-        }
+        self.process_updates();
+        self.update_idle();
         Poll::Pending
     }
 }
@@ -203,6 +549,97 @@ impl Drop for Timer {
     }
 }
 
+/// A future which resolves once every delay bound to a shut down `Timer` has
+/// been dropped, created by `Timer::into_shutdown`.
+///
+/// It never resolves while any `Delay` bound to the timer is still alive,
+/// including one which has never been polled, so the owners of those delays
+/// must drop them for shutdown to complete.
+pub struct ShutdownGuard {
+    inner: Arc<Inner>,
+}
+
+impl Future for ShutdownGuard {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.inner.waker.register(cx.waker());
+        if self.inner.live.load(SeqCst) == 0 {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl fmt::Debug for ShutdownGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("ShutdownGuard")
+            .field("live", &self.inner.live.load(SeqCst))
+            .finish()
+    }
+}
+
+/// A future which resolves once a timer has nothing scheduled and no updates
+/// pending, or has gone away, for `global_idle`.
+pub(crate) struct IdleWait {
+    handle: TimerHandle,
+    id: Option<usize>,
+}
+
+impl IdleWait {
+    pub(crate) fn new(handle: TimerHandle) -> IdleWait {
+        IdleWait { handle, id: None }
+    }
+
+    /// Takes this future's waker out of its timer, if it registered one.
+    fn unregister(&mut self) {
+        let id = match self.id.take() {
+            Some(id) => id,
+            None => return,
+        };
+        if let Some(inner) = self.handle.inner.upgrade() {
+            inner.idle_waiters.lock().unwrap().wakers.remove(&id);
+        }
+    }
+}
+
+impl Future for IdleWait {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let inner = match self.handle.inner.upgrade() {
+            Some(inner) => inner,
+            None => return Poll::Ready(()),
+        };
+        {
+            let mut waiters = inner.idle_waiters.lock().unwrap();
+            let id = match self.id {
+                Some(id) => id,
+                None => {
+                    let id = waiters.next_id;
+                    waiters.next_id += 1;
+                    self.id = Some(id);
+                    id
+                }
+            };
+            waiters.wakers.insert(id, cx.waker().clone());
+        }
+        if inner.idle.load(SeqCst) && inner.list.is_empty() {
+            self.unregister();
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for IdleWait {
+    fn drop(&mut self) {
+        self.unregister();
+    }
+}
+
 impl fmt::Debug for Timer {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         f.debug_struct("Timer").field("heap", &"...").finish()
@@ -218,44 +655,40 @@ impl Default for Timer {
 static HANDLE_FALLBACK: AtomicPtr<Inner> = AtomicPtr::new(EMPTY_HANDLE);
 const EMPTY_HANDLE: *mut Inner = std::ptr::null_mut();
 
-/// Error returned from `TimerHandle::set_fallback`.
-#[derive(Clone, Debug)]
-struct SetDefaultError(());
-
 impl TimerHandle {
-    /// Configures this timer handle to be the one returned by
-    /// `TimerHandle::default`.
-    ///
-    /// By default a global thread is initialized on the first call to
-    /// `TimerHandle::default`. This first call can happen transitively through
-    /// `Delay::new`. If, however, that hasn't happened yet then the global
-    /// default timer handle can be configured through this method.
-    ///
-    /// This method can be used to prevent the global helper thread from
-    /// spawning. If this method is successful then the global helper thread
-    /// will never get spun up.
+    /// Returns the number of timers currently scheduled on the `Timer` this
+    /// handle refers to, that is the delays bound to it which haven't fired
+    /// yet.
     ///
-    /// On success this timer handle will have installed itself globally to be
-    /// used as the return value for `TimerHandle::default` unless otherwise
-    /// specified.
+    /// Returns `None` if the `Timer` has since been dropped. See `Timer::len`
+    /// for exactly what is counted.
+    pub fn len(&self) -> Option<usize> {
+        self.inner
+            .upgrade()
+            .map(|inner| inner.scheduled.load(SeqCst))
+    }
+
+    /// Returns whether the `Timer` this handle refers to has no timers
+    /// scheduled, as counted by `Timer::len`, or `None` if the `Timer` has
+    /// since been dropped.
+    pub fn is_empty(&self) -> Option<bool> {
+        self.len().map(|len| len == 0)
+    }
+
+    /// Returns whether the `Timer` this handle refers to is still alive.
     ///
-    /// # Errors
+    /// Delays created from a handle whose timer is gone are inert.
+    pub fn is_alive(&self) -> bool {
+        self.inner.upgrade().is_some()
+    }
+
+    /// Returns a handle to the global timer used by `Delay::new`.
     ///
-    /// If another thread has already called `set_as_global_fallback` or this
-    /// thread otherwise loses a race to call this method then it will fail
-    /// returning an error. Once a call to `set_as_global_fallback` is
-    /// successful then no future calls may succeed.
-    fn set_as_global_fallback(self) -> Result<(), SetDefaultError> {
-        unsafe {
-            let val = self.into_raw();
-            match HANDLE_FALLBACK.compare_exchange(EMPTY_HANDLE, val, SeqCst, SeqCst) {
-                Ok(_) => Ok(()),
-                Err(_) => {
-                    drop(TimerHandle::from_raw(val));
-                    Err(SetDefaultError(()))
-                }
-            }
-        }
+    /// The first call spins up the global timer's helper thread if it isn't
+    /// already running. Delays created from the returned handle share that
+    /// thread with all other delays created through the default constructors.
+    pub fn global() -> TimerHandle {
+        TimerHandle::default()
     }
 
     fn into_raw(self) -> *mut Inner {
@@ -266,47 +699,64 @@ impl TimerHandle {
         let inner = Weak::from_raw(val);
         TimerHandle { inner }
     }
-}
 
-impl Default for TimerHandle {
-    fn default() -> TimerHandle {
-        let mut fallback = HANDLE_FALLBACK.load(SeqCst);
+    /// Makes `driver`'s timer the global timer, storing the driver in `slot`
+    /// which must be the locked contents of `global::HELPER`.
+    pub(crate) fn install_global(
+        slot: &mut Option<Box<dyn TimerDriver>>,
+        driver: Box<dyn TimerDriver>,
+    ) -> TimerHandle {
+        let ret = driver.handle();
+
+        // Any previous fallback may still be in the middle of being reified
+        // by another thread, so it's intentionally leaked rather than freed.
+        HANDLE_FALLBACK.store(ret.clone().into_raw(), SeqCst);
+        *slot = Some(driver);
+        ret
+    }
 
-        // If the fallback hasn't been previously initialized then let's spin
-        // up a helper thread and try to initialize with that. If we can't
-        // actually create a helper thread then we'll just return a "defunkt"
-        // handle which will return errors when timer objects are attempted to
-        // be associated.
+    /// Reifies the global fallback handle, returning it only if the timer it
+    /// refers to is still alive.
+    pub(crate) fn fallback() -> Option<TimerHandle> {
+        let fallback = HANDLE_FALLBACK.load(SeqCst);
         if fallback == EMPTY_HANDLE {
-            let helper = match global::HelperThread::new() {
-                Ok(helper) => helper,
-                Err(_) => return TimerHandle { inner: Weak::new() },
-            };
-
-            // If we successfully set ourselves as the actual fallback then we
-            // want to `forget` the helper thread to ensure that it persists
-            // globally. If we fail to set ourselves as the fallback that means
-            // that someone was racing with this call to
-            // `TimerHandle::default`.  They ended up winning so we'll destroy
-            // our helper thread (which shuts down the thread) and reload the
-            // fallback.
-            if helper.handle().set_as_global_fallback().is_ok() {
-                let ret = helper.handle();
-                helper.forget();
-                return ret;
-            }
-            fallback = HANDLE_FALLBACK.load(SeqCst);
+            return None;
         }
 
-        // At this point our fallback handle global was configured so we use
-        // its value to reify a handle, clone it, and then forget our reified
-        // handle as we don't actually have an owning reference to it.
-        assert!(fallback != EMPTY_HANDLE);
-        unsafe {
+        // Reify a handle from the global, clone it, and then forget our
+        // reified handle as we don't actually have an owning reference to it.
+        let ret = unsafe {
             let handle = TimerHandle::from_raw(fallback);
             let ret = handle.clone();
             let _ = handle.into_raw();
             ret
+        };
+        if ret.inner.strong_count() == 0 {
+            return None;
+        }
+        Some(ret)
+    }
+}
+
+impl Default for TimerHandle {
+    fn default() -> TimerHandle {
+        if let Some(handle) = TimerHandle::fallback() {
+            return handle;
+        }
+
+        // If the fallback hasn't been initialized, or the helper thread behind
+        // it has been shut down, then spin up a new helper thread. The lock
+        // ensures only one thread gets spawned if several race here. If we
+        // can't actually create a helper thread then we'll just return a
+        // "defunkt" handle which will return errors when timer objects are
+        // attempted to be associated.
+        let mut slot = global::HELPER.lock().unwrap();
+        if let Some(handle) = TimerHandle::fallback() {
+            return handle;
+        }
+        match global::HelperThread::new() {
+            Ok(helper) => TimerHandle::install_global(&mut slot, Box::new(helper)),
+            Err(_) => TimerHandle { inner: Weak::new() },
         }
     }
 }
@@ -318,3 +768,151 @@ impl fmt::Debug for TimerHandle {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::super::Delay;
+    use super::Timer;
+
+    #[test]
+    fn len() {
+        let timer = Timer::new();
+        let handle = timer.handle();
+        assert!(timer.is_empty());
+
+        let at = Instant::now() + Duration::from_secs(3600);
+        let a = Delay::new_at_handle(at, timer.handle());
+        let mut b = Delay::new_at_handle(at, timer.handle());
+        let _c = Delay::new_at_handle(at, timer.handle());
+        assert_eq!(timer.len(), 3);
+        assert_eq!(handle.len(), Some(3));
+
+        drop(a);
+        assert_eq!(timer.len(), 2);
+
+        // Fired delays aren't counted, even before they're dropped.
+        b.fire_immediately();
+        assert_eq!(timer.len(), 1);
+        b.reset_at(at);
+        assert_eq!(timer.len(), 2);
+        drop(b);
+        assert_eq!(timer.len(), 1);
+
+        drop(timer);
+        assert_eq!(handle.len(), None);
+    }
+}
+
+#[cfg(all(test, futures_timer_loom))]
+mod loom_tests {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+    use std::time::{Duration, Instant};
+
+    use futures::task::{noop_waker, waker, ArcWake};
+    use loom::sync::atomic::{AtomicBool, Ordering::SeqCst};
+    use loom::thread;
+
+    use super::super::Delay;
+    use super::Timer;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    /// Records whether it's been woken.
+    struct Flag(AtomicBool);
+
+    impl ArcWake for Flag {
+        fn wake_by_ref(me: &Arc<Self>) {
+            me.0.store(true, SeqCst);
+        }
+    }
+
+    /// Processes any pending updates, as the timer's task would.
+    fn process(timer: &mut Timer) {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(Pin::new(timer).poll(&mut cx).is_pending());
+    }
+
+    fn poll(delay: &mut Delay, waker: &std::task::Waker) -> Poll<()> {
+        Pin::new(delay).poll(&mut Context::from_waker(waker))
+    }
+
+    /// A reset to a later deadline racing with the timer processing updates
+    /// and firing must never leave the delay fired at its old deadline.
+    #[test]
+    fn reset_races_timer() {
+        loom::model(|| {
+            let now = Instant::now();
+            let mut timer = Timer::new();
+            // Left queued, so the timer can pick the node up mid-reset.
+            let mut delay = Delay::new_at_handle(now + HOUR, timer.handle());
+
+            let reset = thread::spawn(move || {
+                delay.reset_at(now + 3 * HOUR);
+                delay
+            });
+            process(&mut timer);
+            timer.advance_to(now + 2 * HOUR);
+            let mut delay = reset.join().unwrap();
+
+            process(&mut timer);
+            timer.advance_to(now + 2 * HOUR);
+            assert!(poll(&mut delay, &noop_waker()).is_pending());
+        });
+    }
+
+    /// A task which polled a delay and then reset it to an earlier deadline,
+    /// while the timer runs concurrently, must be woken once that deadline
+    /// passes.
+    #[test]
+    fn reset_after_register_wakes() {
+        loom::model(|| {
+            let now = Instant::now();
+            let mut timer = Timer::new();
+            let mut delay = Delay::new_at_handle(now + 3 * HOUR, timer.handle());
+            process(&mut timer);
+
+            let woken = Arc::new(Flag(AtomicBool::new(false)));
+            let flag = woken.clone();
+            let task = thread::spawn(move || {
+                assert!(poll(&mut delay, &waker(flag)).is_pending());
+                delay.reset_at(now + HOUR);
+                delay
+            });
+            process(&mut timer);
+            timer.advance_to(now + 2 * HOUR);
+            let _delay = task.join().unwrap();
+
+            process(&mut timer);
+            timer.advance_to(now + 2 * HOUR);
+            assert!(woken.0.load(SeqCst));
+        });
+    }
+
+    /// A poll racing with the timer firing the delay either sees it fired or
+    /// has its waker woken.
+    #[test]
+    fn poll_races_fire() {
+        loom::model(|| {
+            let now = Instant::now();
+            let mut timer = Timer::new();
+            let mut delay = Delay::new_at_handle(now + HOUR, timer.handle());
+            process(&mut timer);
+
+            let woken = Arc::new(Flag(AtomicBool::new(false)));
+            let flag = woken.clone();
+            let task = thread::spawn(move || {
+                let ready = poll(&mut delay, &waker(flag)).is_ready();
+                (ready, delay)
+            });
+            timer.advance_to(now + HOUR);
+            let (ready, _delay) = task.join().unwrap();
+            assert!(ready || woken.0.load(SeqCst));
+        });
+    }
+}
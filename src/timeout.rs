@@ -0,0 +1,292 @@
+//! Support for requiring a future to complete before a deadline.
+
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm-bindgen"))]
+use instant::Instant;
+#[cfg(not(all(target_arch = "wasm32", feature = "wasm-bindgen")))]
+use std::time::Instant;
+
+use crate::Delay;
+
+/// Requires `future` to complete within `dur`.
+///
+/// The returned future resolves to the output of `future` if it completes
+/// first, or to an `Elapsed` error if `dur` passes first. Any `Future` can be
+/// used, its output doesn't need to be a `Result`.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[async_std::main]
+/// # async fn main() {
+/// use std::time::Duration;
+/// use futures_timer::timeout;
+///
+/// let some_future = async { 42 };
+/// match timeout(Duration::from_secs(1), some_future).await {
+///     Ok(n) => println!("got {}", n),
+///     Err(e) => println!("{}", e),
+/// }
+/// # }
+/// ```
+pub fn timeout<F: Future>(dur: Duration, future: F) -> Timeout<F> {
+    Timeout {
+        future: Some(future),
+        delay: Delay::new(dur),
+        cancel: false,
+    }
+}
+
+/// Requires `future` to complete before the instant `at`.
+///
+/// This is like `timeout`, but with an absolute deadline, which is handy when
+/// one deadline is shared by several operations. The inner future is always
+/// polled first, so a future which is ready wins over a deadline which has
+/// already passed.
+pub fn with_deadline<F: Future>(at: Instant, future: F) -> Timeout<F> {
+    Timeout {
+        future: Some(future),
+        delay: Delay::new_at(at),
+        cancel: false,
+    }
+}
+
+/// Requires `future` to complete before `delay` fires.
+///
+/// The delay's deadline governs the timeout, so timeouts built from delays
+/// sharing one deadline, for example clones of the same `Delay`, elapse at
+/// exactly the same instant.
+pub fn timeout_with<F: Future>(delay: Delay, future: F) -> Timeout<F> {
+    Timeout {
+        future: Some(future),
+        delay,
+        cancel: false,
+    }
+}
+
+/// Like `timeout`, but drops `future` as soon as the deadline elapses.
+///
+/// A `Timeout` created by `timeout` keeps the inner future alive until the
+/// `Timeout` itself is dropped, even once it has resolved to `Elapsed`. This
+/// instead drops the inner future in place the moment the deadline is
+/// observed, which releases any resources it holds straight away. The inner
+/// future is then no longer available through `get_ref` and friends.
+pub fn timeout_and_cancel<F: Future>(dur: Duration, future: F) -> Timeout<F> {
+    Timeout {
+        cancel: true,
+        ..timeout(dur, future)
+    }
+}
+
+/// A future which requires an inner future to complete before a deadline.
+///
+/// This is created by the `timeout`, `with_deadline`, `timeout_with` and
+/// `timeout_and_cancel` functions.
+pub struct Timeout<F> {
+    // Only ever `None` once a `timeout_and_cancel` has elapsed.
+    future: Option<F>,
+    delay: Delay,
+    cancel: bool,
+}
+
+impl<F> Timeout<F> {
+    /// Returns a reference to the inner future.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the inner future was dropped because a timeout created by
+    /// `timeout_and_cancel` elapsed.
+    pub fn get_ref(&self) -> &F {
+        self.future.as_ref().expect(CANCELLED)
+    }
+
+    /// Returns a mutable reference to the inner future.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the inner future was dropped because a timeout created by
+    /// `timeout_and_cancel` elapsed.
+    pub fn get_mut(&mut self) -> &mut F {
+        self.future.as_mut().expect(CANCELLED)
+    }
+
+    /// Consumes this `Timeout`, returning the inner future.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the inner future was dropped because a timeout created by
+    /// `timeout_and_cancel` elapsed.
+    pub fn into_inner(self) -> F {
+        self.future.expect(CANCELLED)
+    }
+}
+
+impl<F> fmt::Debug for Timeout<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Timeout")
+            .field("delay", &self.delay)
+            .field("future", &"...")
+            .finish()
+    }
+}
+
+const CANCELLED: &str = "inner future was dropped when the timeout elapsed";
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: `future` is never moved out of a pinned `Timeout`, and
+        // `Delay` is `Unpin` so it doesn't need to stay pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let mut future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        // Poll the inner future first so that a future which is ready wins
+        // over a deadline which has already elapsed.
+        if let Some(f) = future.as_mut().as_pin_mut() {
+            if let Poll::Ready(v) = f.poll(cx) {
+                return Poll::Ready(Ok(v));
+            }
+        }
+        match Pin::new(&mut this.delay).poll(cx) {
+            Poll::Ready(()) => {
+                if this.cancel {
+                    // Dropping in place is fine for a pinned future.
+                    future.set(None);
+                }
+                Poll::Ready(Err(Elapsed(())))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// An idle timeout to check from inside a hand-written `poll`.
+///
+/// `Timeout` needs to own the future it guards, which isn't always possible,
+/// say when the future is pinned in place elsewhere. A guard instead sits
+/// alongside the work: call `poll_expired` from your own `poll` to learn
+/// when the timeout has passed, and `reset_on_activity` whenever progress is
+/// made to push it back.
+///
+/// # Examples
+///
+/// ```
+/// use std::future::Future;
+/// use std::pin::Pin;
+/// use std::task::{Context, Poll};
+/// use std::time::Duration;
+/// use futures_timer::TimeoutGuard;
+///
+/// struct Connection {
+///     idle: TimeoutGuard,
+///     // ...
+/// }
+///
+/// impl Future for Connection {
+///     type Output = ();
+///
+///     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+///         // ... on every message received:
+///         //     self.idle.reset_on_activity();
+///         if self.idle.poll_expired(cx).is_ready() {
+///             return Poll::Ready(()); // closed for being idle
+///         }
+///         Poll::Pending
+///     }
+/// }
+/// # let _ = Connection { idle: TimeoutGuard::new(Duration::from_secs(30)) };
+/// ```
+#[derive(Debug)]
+pub struct TimeoutGuard {
+    delay: Delay,
+    idle: Duration,
+}
+
+impl TimeoutGuard {
+    /// Creates a guard which expires once `idle` passes without activity.
+    pub fn new(idle: Duration) -> TimeoutGuard {
+        TimeoutGuard {
+            delay: Delay::new(idle),
+            idle,
+        }
+    }
+
+    /// Returns `Ready` once the timeout has passed, registering the current
+    /// task to be woken when it does otherwise.
+    pub fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        Pin::new(&mut self.delay).poll(cx)
+    }
+
+    /// Re-arms the timeout to expire `idle` from now.
+    ///
+    /// A task which already polled the guard stays registered, so there's no
+    /// need to poll it again just to keep receiving the wakeup.
+    pub fn reset_on_activity(&mut self) {
+        self.delay.reset(self.idle);
+    }
+}
+
+/// Error returned by `Timeout` when its deadline elapses before the inner
+/// future completes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Elapsed(());
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("deadline has elapsed")
+    }
+}
+
+impl Error for Elapsed {}
+
+impl From<Elapsed> for io::Error {
+    fn from(err: Elapsed) -> io::Error {
+        io::Error::new(io::ErrorKind::TimedOut, err)
+    }
+}
+
+/// Requires `future` to complete within `dur`, substituting the output of
+/// `fallback` if it doesn't.
+///
+/// This saves matching on `Elapsed` just to supply a default. The inner
+/// future is dropped when the deadline passes, and `fallback` is only called
+/// then.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[async_std::main]
+/// # async fn main() {
+/// use std::time::Duration;
+/// use futures_timer::timeout_or_else;
+///
+/// let lookup = async { Some("cached") };
+/// let value = timeout_or_else(Duration::from_secs(1), lookup, || None).await;
+/// # drop(value);
+/// # }
+/// ```
+pub async fn timeout_or_else<F, G>(dur: Duration, future: F, fallback: G) -> F::Output
+where
+    F: Future,
+    G: FnOnce() -> F::Output,
+{
+    match timeout(dur, future).await {
+        Ok(output) => output,
+        Err(Elapsed(())) => fallback(),
+    }
+}
+
+/// Returns the canonical error for a deadline which has been reached.
+///
+/// The error is of kind `io::ErrorKind::TimedOut` and wraps an `Elapsed`.
+pub fn elapsed_error() -> io::Error {
+    Elapsed(()).into()
+}
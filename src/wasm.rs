@@ -1,23 +1,72 @@
 //! A version of `Delay` that works on wasm.
 
 use gloo_timers::future::TimeoutFuture;
+use instant::Instant;
 use send_wrapper::SendWrapper;
 use std::{
     future::Future,
     pin::Pin,
     task::{Context, Poll},
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
 /// A version of `Delay` that works on wasm.
+///
+/// Timeouts are handed to the browser's `setTimeout` in whole milliseconds,
+/// so any sub-millisecond precision of the requested duration is lost, and
+/// browsers may clamp short or nested timeouts further. Durations under a
+/// millisecond skip `setTimeout` entirely: the delay yields once to the
+/// executor, which reschedules the task on the next microtask, rather than
+/// waiting for a turn of the event loop.
+///
+/// Unlike the native `Delay`, this one is tied to the thread which created
+/// it. It's `Send` and `Sync` so that it fits the same APIs, but polling or
+/// dropping it from any other thread panics.
 #[derive(Debug)]
-pub struct Delay(SendWrapper<TimeoutFuture>);
+pub struct Delay(Inner);
+
+#[derive(Debug)]
+enum Inner {
+    Timeout(SendWrapper<TimeoutFuture>),
+    // Whether the delay has already yielded once.
+    Yield(bool),
+}
 
 impl Delay {
     /// Creates a new future which will fire at `dur` time into the future.
     #[inline]
     pub fn new(dur: Duration) -> Delay {
-        Self(SendWrapper::new(TimeoutFuture::new(dur.as_millis() as u32)))
+        if dur < Duration::from_millis(1) {
+            return Self(Inner::Yield(false));
+        }
+        Self(Inner::Timeout(SendWrapper::new(TimeoutFuture::new(
+            dur.as_millis() as u32,
+        ))))
+    }
+
+    /// Creates a new future which will fire at the time specified by `at`.
+    ///
+    /// If `at` is in the past the returned future will fire as soon as
+    /// possible.
+    #[inline]
+    pub fn new_at(at: Instant) -> Delay {
+        Delay::new(at.saturating_duration_since(Instant::now()))
+    }
+
+    /// Creates a new future which will fire at the wall-clock time `t`.
+    ///
+    /// If `t` is at or before the current system time the returned future
+    /// will fire as soon as possible.
+    #[inline]
+    pub fn until_system_time(t: SystemTime) -> Delay {
+        // `SystemTime::now` isn't available in the browser, so the current
+        // time comes from `instant` and both sides are compared as durations
+        // since the epoch instead.
+        let now = instant::SystemTime::now()
+            .duration_since(instant::SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        let at = t.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+        Delay::new(at.saturating_sub(now))
     }
 
     /// Resets the timeout.
@@ -25,12 +74,29 @@ impl Delay {
     pub fn reset(&mut self, dur: Duration) {
         *self = Delay::new(dur);
     }
+
+    /// Resets the timeout to fire at the time specified by `at`.
+    ///
+    /// This is best-effort: the remaining time is computed against
+    /// `Instant::now()` and an instant in the past fires as soon as possible.
+    #[inline]
+    pub fn reset_at(&mut self, at: Instant) {
+        self.reset(at.saturating_duration_since(Instant::now()));
+    }
 }
 
 impl Future for Delay {
     type Output = ();
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
-        Pin::new(&mut *Pin::into_inner(self).0).poll(cx)
+        match &mut Pin::into_inner(self).0 {
+            Inner::Timeout(timeout) => Pin::new(&mut **timeout).poll(cx),
+            Inner::Yield(true) => Poll::Ready(()),
+            Inner::Yield(yielded) => {
+                *yielded = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
     }
 }
@@ -0,0 +1,24 @@
+use std::time::{Duration, Instant};
+
+use futures::FutureExt;
+use futures_timer::CoarseTimer;
+
+#[async_std::test]
+async fn shared_bucket() {
+    let timer = CoarseTimer::new(Duration::from_secs(1));
+    let i = Instant::now();
+    let dur = Duration::from_millis(100);
+    let delays = (0..1000).map(|_| timer.delay(dur)).collect::<Vec<_>>();
+    assert_eq!(timer.len(), 1);
+
+    futures::future::join_all(delays).await;
+    assert!(i.elapsed() >= dur);
+    assert!(timer.is_empty());
+}
+
+#[test]
+fn far_future() {
+    let timer = CoarseTimer::new(Duration::from_secs(1));
+    let d = timer.delay(Duration::from_secs(u64::MAX / 4));
+    assert!(d.now_or_never().is_none());
+}
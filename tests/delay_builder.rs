@@ -0,0 +1,66 @@
+use std::time::{Duration, Instant};
+
+use futures::{poll, FutureExt};
+use futures_timer::{DelayBuilder, Timer};
+
+#[async_std::test]
+async fn after() {
+    let i = Instant::now();
+    let dur = Duration::from_millis(100);
+    DelayBuilder::new().after(dur).build().unwrap().await;
+    assert!(i.elapsed() >= dur);
+}
+
+#[test]
+fn default_fires_immediately() {
+    let d = DelayBuilder::new().build().unwrap();
+    assert_eq!(d.now_or_never(), Some(()));
+}
+
+#[test]
+fn past_deadline() {
+    let past = Instant::now() - Duration::from_secs(3600);
+    let d = DelayBuilder::new().deadline(past).build().unwrap();
+    assert_eq!(d.now_or_never(), Some(()));
+
+    let err = DelayBuilder::new()
+        .deadline(past)
+        .fire_if_past(false)
+        .build()
+        .unwrap_err();
+    assert_eq!(err.to_string(), "deadline is in the past");
+
+    // A deadline of "now" has passed by the time it's checked, but only
+    // just, so it's clamped rather than rejected.
+    let d = DelayBuilder::new()
+        .deadline(Instant::now())
+        .fire_if_past(false)
+        .build()
+        .unwrap();
+    assert_eq!(d.now_or_never(), Some(()));
+
+    let future = Instant::now() + Duration::from_secs(3600);
+    let d = DelayBuilder::new()
+        .deadline(future)
+        .fire_if_past(false)
+        .build()
+        .unwrap();
+    assert!(d.now_or_never().is_none());
+}
+
+#[async_std::test]
+async fn handle() {
+    let mut timer = Timer::new();
+    let at = Instant::now() + Duration::from_secs(3600);
+    let mut d = DelayBuilder::new()
+        .deadline(at)
+        .handle(timer.handle())
+        .build()
+        .unwrap();
+    assert!(poll!(&mut d).is_pending());
+
+    // Only the local timer can fire it.
+    assert!(poll!(&mut timer).is_pending());
+    timer.advance_to(at);
+    assert!(poll!(&mut d).is_ready());
+}
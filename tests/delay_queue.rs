@@ -0,0 +1,59 @@
+use std::time::{Duration, Instant};
+
+use futures::future::poll_fn;
+use futures_timer::DelayQueue;
+
+#[async_std::test]
+async fn expires_in_order() {
+    let mut queue = DelayQueue::new();
+    queue.insert("c", Duration::from_millis(30));
+    queue.insert("a", Duration::from_millis(10));
+    queue.insert("b", Duration::from_millis(20));
+    assert_eq!(queue.len(), 3);
+
+    let mut order = Vec::new();
+    while let Some(expired) = poll_fn(|cx| queue.poll_expired(cx)).await {
+        assert!(expired.deadline() <= Instant::now());
+        order.push(expired.into_inner());
+    }
+    assert_eq!(order, ["a", "b", "c"]);
+    assert!(queue.is_empty());
+}
+
+#[async_std::test]
+async fn reset() {
+    let mut queue = DelayQueue::new();
+    let a = queue.insert("a", Duration::from_millis(10));
+    queue.insert("b", Duration::from_millis(20));
+    queue.reset(&a, Duration::from_millis(30));
+
+    let first = poll_fn(|cx| queue.poll_expired(cx)).await.unwrap();
+    assert_eq!(*first.get_ref(), "b");
+    let second = poll_fn(|cx| queue.poll_expired(cx)).await.unwrap();
+    assert_eq!(second.key(), a);
+    assert_eq!(second.into_inner(), "a");
+}
+
+#[async_std::test]
+async fn remove() {
+    let mut queue = DelayQueue::new();
+    let a = queue.insert(1, Duration::from_secs(3600));
+    let b = queue.insert(2, Duration::from_millis(10));
+    assert_eq!(queue.remove(&a).into_inner(), 1);
+    assert_eq!(queue.len(), 1);
+
+    // The removed key's storage is reused.
+    let c = queue.insert(3, Duration::from_millis(20));
+    assert_eq!(c, a);
+
+    let expired = poll_fn(|cx| queue.poll_expired(cx)).await.unwrap();
+    assert_eq!(expired.key(), b);
+    assert_eq!(
+        poll_fn(|cx| queue.poll_expired(cx))
+            .await
+            .unwrap()
+            .into_inner(),
+        3
+    );
+    assert!(poll_fn(|cx| queue.poll_expired(cx)).await.is_none());
+}
@@ -0,0 +1,53 @@
+use std::time::{Duration, Instant};
+
+use futures::future::poll_fn;
+use futures_timer::DelaySet;
+
+#[async_std::test]
+async fn expires_in_order() {
+    let now = Instant::now();
+    let mut set = DelaySet::new();
+    set.insert("c", now + Duration::from_millis(30));
+    set.insert("a", now + Duration::from_millis(10));
+    set.insert("b", now + Duration::from_millis(20));
+    assert_eq!(set.len(), 3);
+
+    assert_eq!(poll_fn(|cx| set.poll_expired(cx)).await, Some("a"));
+    assert!(now.elapsed() >= Duration::from_millis(10));
+    assert_eq!(poll_fn(|cx| set.poll_expired(cx)).await, Some("b"));
+    assert_eq!(poll_fn(|cx| set.poll_expired(cx)).await, Some("c"));
+    assert_eq!(poll_fn(|cx| set.poll_expired(cx)).await, None);
+}
+
+#[async_std::test]
+async fn remove() {
+    let now = Instant::now();
+    let mut set = DelaySet::new();
+    set.insert(1, now + Duration::from_millis(10));
+    set.insert(2, now + Duration::from_millis(20));
+    assert!(set.remove(&1));
+    assert!(!set.remove(&1));
+
+    assert_eq!(poll_fn(|cx| set.poll_expired(cx)).await, Some(2));
+    assert!(set.is_empty());
+}
+
+#[async_std::test]
+async fn batch() {
+    let now = Instant::now();
+    let at = now + Duration::from_millis(20);
+    let mut set = DelaySet::new();
+    for i in 0..50 {
+        set.insert(i, at);
+    }
+    set.insert(50, now + Duration::from_secs(3600));
+
+    let mut batch = poll_fn(|cx| set.poll_expired_batch(cx)).await;
+    assert!(Instant::now() >= at);
+    batch.sort_unstable();
+    assert_eq!(batch, (0..50).collect::<Vec<_>>());
+    assert_eq!(set.len(), 1);
+
+    set.remove(&50);
+    assert!(poll_fn(|cx| set.poll_expired_batch(cx)).await.is_empty());
+}
@@ -0,0 +1,45 @@
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::Context;
+use std::time::{Duration, Instant};
+
+use futures::task::noop_waker;
+use futures_timer::{set_global_driver, yield_now, Delay, Timer, TimerDriver, TimerHandle};
+
+/// A driver whose timer is pumped by hand from the test.
+struct Manual(Arc<Mutex<Timer>>);
+
+impl TimerDriver for Manual {
+    fn handle(&self) -> TimerHandle {
+        self.0.lock().unwrap().handle()
+    }
+}
+
+#[test]
+fn manual_driver() {
+    let timer = Arc::new(Mutex::new(Timer::new()));
+    set_global_driver(Manual(timer.clone())).unwrap();
+    let err = set_global_driver(Manual(timer.clone())).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut d = Delay::new(Duration::from_secs(3600));
+    assert_eq!(timer.lock().unwrap().len(), 1);
+    assert!(Pin::new(&mut d).poll(&mut cx).is_pending());
+
+    // `yield_now`'s deadline has passed straight away, but it still waits for
+    // the timer.
+    let mut y = Box::pin(yield_now());
+    assert!(y.as_mut().poll(&mut cx).is_pending());
+    assert!(y.as_mut().poll(&mut cx).is_pending());
+
+    // Nothing fires the delay unless the timer is pumped.
+    let mut timer = timer.lock().unwrap();
+    assert!(Pin::new(&mut *timer).poll(&mut cx).is_pending());
+    timer.advance_to(Instant::now() + Duration::from_secs(3600));
+    assert!(Pin::new(&mut d).poll(&mut cx).is_ready());
+    assert!(y.as_mut().poll(&mut cx).is_ready());
+}
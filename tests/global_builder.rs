@@ -0,0 +1,40 @@
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::Context;
+use std::thread;
+use std::time::Duration;
+
+use futures::task::{waker, ArcWake};
+use futures_timer::{Delay, GlobalTimerBuilder};
+
+/// Records the name of the thread the waker was woken on.
+struct WokenOn(Mutex<Option<String>>);
+
+impl ArcWake for WokenOn {
+    fn wake_by_ref(me: &Arc<Self>) {
+        *me.0.lock().unwrap() = thread::current().name().map(String::from);
+    }
+}
+
+#[test]
+fn custom_thread_name() {
+    GlobalTimerBuilder::new()
+        .thread_name("my-timer")
+        .stack_size(256 * 1024)
+        .build_global()
+        .unwrap();
+    let err = GlobalTimerBuilder::new().build_global().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+
+    // Delays are woken from the helper thread as they fire.
+    let woken = Arc::new(WokenOn(Mutex::new(None)));
+    let waker = waker(woken.clone());
+    let mut cx = Context::from_waker(&waker);
+    let mut d = Delay::new(Duration::from_millis(10));
+    assert!(Pin::new(&mut d).poll(&mut cx).is_pending());
+    thread::sleep(Duration::from_millis(200));
+    assert!(Pin::new(&mut d).poll(&mut cx).is_ready());
+    assert_eq!(woken.0.lock().unwrap().as_deref(), Some("my-timer"));
+}
@@ -0,0 +1,26 @@
+use std::time::{Duration, Instant};
+
+use futures::{poll, FutureExt};
+use futures_timer::{global_idle, yield_now, Delay};
+
+#[async_std::test]
+async fn idle_after_delays_finish() {
+    // Not running yet, so trivially idle.
+    assert!(global_idle().now_or_never().is_some());
+
+    let mut far = Delay::new(Duration::from_secs(3600));
+    assert!(poll!(&mut far).is_pending());
+    let mut idle = Box::pin(global_idle());
+    yield_now().await;
+    assert!(poll!(idle.as_mut()).is_pending());
+    drop(far);
+
+    let start = Instant::now();
+    idle.await;
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    Delay::new(Duration::from_millis(10)).await;
+    let start = Instant::now();
+    global_idle().await;
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
@@ -0,0 +1,43 @@
+use std::time::{Duration, Instant};
+
+use futures_timer::heap::{Heap, TimerEntry};
+
+#[test]
+fn insert_remove_by_slot() {
+    let now = Instant::now();
+    let mut heap = Heap::new();
+    let slots = (0..10u64)
+        .map(|i| heap.push(TimerEntry::new(now + Duration::from_secs(10 - i), i)))
+        .collect::<Vec<_>>();
+    assert_eq!(heap.len(), 10);
+
+    // Remove every other entry through its slot.
+    for (i, slot) in slots.into_iter().enumerate() {
+        if i % 2 == 0 {
+            assert_eq!(heap.remove(slot).unwrap().value, i as u64);
+        }
+    }
+    assert_eq!(heap.len(), 5);
+
+    let mut order = Vec::new();
+    while let Some(entry) = heap.pop() {
+        order.push(entry.value);
+    }
+    assert_eq!(order, [9, 7, 5, 3, 1]);
+    assert!(heap.is_empty());
+}
+
+#[test]
+fn stale_slot() {
+    let now = Instant::now();
+    let mut heap = Heap::new();
+    let stale = heap.push(TimerEntry::new(now, "popped"));
+    assert_eq!(heap.pop().unwrap().value, "popped");
+
+    // The popped entry's slot is reused by the next push, but the stale slot
+    // must not remove it.
+    let slot = heap.push(TimerEntry::new(now, "pushed"));
+    assert!(heap.remove(stale).is_none());
+    assert_eq!(heap.len(), 1);
+    assert_eq!(heap.remove(slot).unwrap().value, "pushed");
+}
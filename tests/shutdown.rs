@@ -0,0 +1,13 @@
+use std::time::Duration;
+
+use futures_timer::{shutdown_global_timer, Delay};
+
+#[async_std::test]
+async fn respawn_after_shutdown() {
+    let dur = Duration::from_millis(10);
+    Delay::new(dur).await;
+    shutdown_global_timer();
+
+    Delay::new(dur).await;
+    shutdown_global_timer();
+}
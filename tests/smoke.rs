@@ -1,8 +1,10 @@
 use std::error::Error;
+use std::future::Future;
 use std::pin::Pin;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
-use futures_timer::Delay;
+use futures::{poll, FutureExt};
+use futures_timer::{boxed_delay, sleep, yield_now, Delay};
 
 #[async_std::test]
 async fn works() {
@@ -29,3 +31,381 @@ async fn reset() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
     assert!(i.elapsed() > dur);
     Ok(())
 }
+
+#[async_std::test]
+async fn reset_at() {
+    let i = Instant::now();
+    let dur = Duration::from_millis(100);
+    let mut d = Delay::new(Duration::from_secs(3600));
+    d.reset_at(i + dur);
+    d.await;
+    assert!(i.elapsed() >= dur);
+}
+
+#[async_std::test]
+async fn new_at() {
+    let i = Instant::now();
+    let dur = Duration::from_millis(100);
+    Delay::new_at(i + dur).await;
+    assert!(i.elapsed() >= dur);
+}
+
+#[async_std::test]
+async fn overshoot() {
+    let overshoot = Delay::new(Duration::from_millis(10)).into_overshoot().await;
+    assert!(overshoot < Duration::from_secs(1));
+}
+
+#[async_std::test]
+async fn clone() {
+    let i = Instant::now();
+    let dur = Duration::from_millis(100);
+    let d = Delay::new(dur);
+    let d2 = d.clone();
+    drop(d);
+    d2.await;
+    assert!(i.elapsed() >= dur);
+}
+
+#[async_std::test]
+async fn pause_resume() {
+    let dur = Duration::from_millis(100);
+    let mut d = Delay::new(dur);
+    d.pause();
+    Delay::new(dur * 2).await;
+    assert!(poll!(&mut d).is_pending());
+
+    let i = Instant::now();
+    d.resume();
+    d.await;
+    assert!(i.elapsed() >= dur / 2);
+}
+
+#[async_std::test]
+async fn clone_paused_after_deadline() {
+    let dur = Duration::from_millis(50);
+    let mut d = Delay::new(dur);
+    d.pause();
+    Delay::new(dur * 2).await;
+
+    let mut d2 = d.clone();
+    assert!(poll!(&mut d).is_pending());
+    assert!(poll!(&mut d2).is_pending());
+
+    d2.resume();
+    d2.await;
+    assert!(poll!(&mut d).is_pending());
+}
+
+#[async_std::test]
+async fn cancel() {
+    let i = Instant::now();
+    let d = Delay::new(Duration::from_secs(3600));
+    let canceller = d.cancel_handle();
+    async_std::task::spawn(async move {
+        Delay::new(Duration::from_millis(10)).await;
+        canceller.cancel();
+    });
+    d.await;
+    assert!(i.elapsed() < Duration::from_secs(60));
+}
+
+#[async_std::test]
+async fn cancel_paused() {
+    let mut d = Delay::new(Duration::from_secs(3600));
+    d.pause();
+    let canceller = d.cancel_handle();
+    assert!(poll!(&mut d).is_pending());
+
+    canceller.cancel();
+    d.await;
+}
+
+#[async_std::test]
+async fn handles_survive_reset_of_elapsed_delay() {
+    let mut d = Delay::new(Duration::from_secs(0));
+    let canceller = d.cancel_handle();
+    let mut notify = d.subscribe_resets();
+
+    d.reset(Duration::from_secs(3600));
+    notify.changed().await;
+    assert!(poll!(&mut d).is_pending());
+
+    canceller.cancel();
+    d.await;
+}
+
+#[test]
+fn zero() {
+    assert!(Delay::new(Duration::from_secs(0)).now_or_never().is_some());
+}
+
+#[async_std::test]
+async fn fire_immediately() {
+    let i = Instant::now();
+    let mut d = Delay::new(Duration::from_secs(3600));
+    d.fire_immediately();
+    d.await;
+    assert!(i.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn past_deadline_now_or_never() {
+    let past = Instant::now() - Duration::from_secs(1);
+    assert_eq!(Delay::new_at(past).now_or_never(), Some(()));
+
+    let mut d = Delay::new(Duration::from_secs(3600));
+    d.reset_at(past);
+    assert_eq!(d.now_or_never(), Some(()));
+}
+
+#[async_std::test]
+async fn reset_at_checked() {
+    let mut d = Delay::new(Duration::from_secs(3600));
+    let past = Instant::now() - Duration::from_secs(3600);
+    assert!(d.reset_at_checked(past).is_err());
+    assert!(poll!(&mut d).is_pending());
+
+    // A deadline which has only just passed is clamped rather than rejected.
+    let just_now = Instant::now() - Duration::from_millis(1);
+    d.reset_at_checked(just_now).unwrap();
+    assert!(d.deadline() >= just_now);
+    (&mut d).await;
+
+    let soon = Instant::now() + Duration::from_millis(10);
+    d.reset_at_checked(soon).unwrap();
+    d.await;
+    assert!(Instant::now() >= soon);
+}
+
+#[async_std::test]
+async fn boxed() {
+    let i = Instant::now();
+    let dur = Duration::from_millis(10);
+    let delays: Vec<Pin<Box<dyn Future<Output = ()> + Send>>> =
+        vec![boxed_delay(dur), boxed_delay(dur * 2)];
+    for d in delays {
+        d.await;
+    }
+    assert!(i.elapsed() >= dur * 2);
+}
+
+#[async_std::test]
+async fn until_system_time() {
+    let past = SystemTime::now() - Duration::from_secs(3600);
+    assert!(Delay::until_system_time(past).now_or_never().is_some());
+    let epoch = SystemTime::UNIX_EPOCH;
+    assert!(Delay::until_system_time(epoch).now_or_never().is_some());
+
+    let i = Instant::now();
+    let dur = Duration::from_millis(50);
+    Delay::until_system_time(SystemTime::now() + dur).await;
+    assert!(i.elapsed() >= dur - Duration::from_millis(5));
+
+    let mut far = Delay::until_system_time(SystemTime::now() + Duration::from_secs(3600));
+    assert!(poll!(&mut far).is_pending());
+}
+
+#[async_std::test]
+async fn timed() {
+    let when = Instant::now() + Duration::from_millis(10);
+    let fired = Delay::new_at(when).timed().await;
+    assert!(fired >= when);
+}
+
+#[async_std::test]
+async fn subscribe_resets() {
+    let mut d = Delay::new(Duration::from_secs(3600));
+    let mut notify = d.subscribe_resets();
+    assert!(poll!(Box::pin(notify.changed())).is_pending());
+
+    let waiter = async_std::task::spawn(async move {
+        notify.changed().await;
+        notify
+    });
+    async_std::task::sleep(Duration::from_millis(10)).await;
+    d.reset(Duration::from_millis(10));
+    let mut notify = waiter.await;
+    assert!(poll!(Box::pin(notify.changed())).is_pending());
+    d.await;
+}
+
+#[async_std::test]
+async fn ticker() {
+    let i = Instant::now();
+    let period = Duration::from_millis(20);
+    let mut ticker = Delay::new(period).into_ticker(period);
+    assert!(poll!(&mut ticker).is_pending());
+    (&mut ticker).await;
+    assert!(i.elapsed() >= period);
+    assert!(poll!(&mut ticker).is_pending());
+    (&mut ticker).await;
+    assert!(i.elapsed() >= period * 2);
+}
+
+#[test]
+fn ord() {
+    let now = Instant::now();
+    let secs = [3, 1, 2];
+    let mut delays = secs
+        .iter()
+        .map(|&s| Delay::new_at(now + Duration::from_secs(s)))
+        .collect::<Vec<_>>();
+    delays.sort();
+    let expected = [1, 2, 3]
+        .iter()
+        .map(|&s| Delay::new_at(now + Duration::from_secs(s)))
+        .collect::<Vec<_>>();
+    assert_eq!(delays, expected);
+    assert!(delays[0] < delays[1]);
+}
+
+#[test]
+fn auto_traits() {
+    use std::panic::{RefUnwindSafe, UnwindSafe};
+
+    static_assertions::assert_impl_all!(Delay: Send, Sync, Unpin, UnwindSafe, RefUnwindSafe);
+}
+
+#[async_std::test]
+async fn yield_now_repolls() {
+    let mut polls = 0;
+    let mut y = Box::pin(yield_now());
+    futures::future::poll_fn(|cx| {
+        polls += 1;
+        y.as_mut().poll(cx)
+    })
+    .await;
+    assert!(polls >= 2);
+}
+
+#[async_std::test]
+async fn race_with() {
+    let i = Instant::now();
+    let short = Duration::from_millis(50);
+    let long = Duration::from_millis(500);
+    Delay::new(long).race_with(Delay::new(short)).await;
+    assert!(i.elapsed() >= short);
+    assert!(i.elapsed() < long);
+}
+
+#[test]
+fn check() {
+    let waker = futures::task::noop_waker();
+    let mut d = Delay::new(Duration::from_millis(10));
+    assert!(!d.check(&waker));
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(d.check(&waker));
+}
+
+#[async_std::test]
+async fn extend() {
+    let i = Instant::now();
+    let dur = Duration::from_millis(20);
+    let mut d = Delay::new(dur);
+    d.extend(dur);
+    d.extend(dur);
+    d.await;
+    assert!(i.elapsed() >= dur * 3);
+}
+
+#[async_std::test]
+async fn map_output() {
+    let d = Delay::new(Duration::from_millis(10));
+    assert_eq!(d.map_output(5).await, 5);
+}
+
+#[async_std::test]
+async fn split_at() {
+    let i = Instant::now();
+    let (mut first, mut second) = Delay::new(Duration::from_millis(200)).split_at(0.5);
+    (&mut first).await;
+    assert!(i.elapsed() >= Duration::from_millis(100));
+    assert!(poll!(&mut second).is_pending());
+    second.await;
+    assert!(i.elapsed() >= Duration::from_millis(200));
+
+    let past = Instant::now() - Duration::from_secs(1);
+    let (first, second) = Delay::new_at(past).split_at(2.0);
+    assert_eq!(first.now_or_never(), Some(()));
+    assert_eq!(second.now_or_never(), Some(()));
+}
+
+#[test]
+fn saturating_remaining_round_trip() {
+    let hour = Duration::from_secs(3600);
+    let d = Delay::new(hour);
+    let saved = d.saturating_remaining();
+    assert!(saved <= hour && saved > hour - Duration::from_secs(60));
+    drop(d);
+
+    let restored = Delay::new(saved);
+    assert!(restored.saturating_remaining() <= saved);
+    assert!(restored.saturating_remaining() > saved - Duration::from_secs(60));
+
+    let past = Delay::new_at(Instant::now() - Duration::from_secs(1));
+    assert_eq!(past.saturating_remaining(), Duration::from_secs(0));
+}
+
+#[test]
+fn weak_delay() {
+    let mut d = Delay::new(Duration::from_secs(3600));
+    let weak = d.downgrade();
+    let mut snapshot = weak.snapshot().unwrap();
+    assert_eq!(snapshot, d);
+
+    // The snapshot is a separate delay.
+    snapshot.fire_immediately();
+    assert!((&mut snapshot).now_or_never().is_some());
+    assert!((&mut d).now_or_never().is_none());
+    drop(snapshot);
+    assert!(weak.is_alive());
+    assert!(weak.snapshot().is_some());
+
+    drop(d);
+    assert!(!weak.is_alive());
+    assert!(weak.snapshot().is_none());
+
+    // Delays which had elapsed when created can be tracked too.
+    let d = Delay::new(Duration::from_secs(0));
+    let weak = d.downgrade();
+    assert!(weak.is_alive());
+    assert!(weak.snapshot().unwrap().now_or_never().is_some());
+    drop(d);
+    assert!(!weak.is_alive());
+}
+
+#[async_std::test]
+async fn sleep_short_and_long() {
+    for &dur in &[Duration::from_micros(10), Duration::from_millis(50)] {
+        let i = Instant::now();
+        sleep(dur).await;
+        assert!(i.elapsed() >= dur);
+    }
+}
+
+#[async_std::test]
+async fn then_delay() {
+    let i = Instant::now();
+    let (a, b) = (Duration::from_millis(50), Duration::from_millis(30));
+    Delay::new(a).then_delay(b).await;
+    assert!(i.elapsed() >= a + b);
+}
+
+#[test]
+fn ids() {
+    use std::collections::HashSet;
+
+    let mut a = Delay::new(Duration::from_secs(3600));
+    let b = Delay::new(Duration::from_secs(3600));
+    let c = a.clone();
+    let mut past = Delay::new_at(Instant::now() - Duration::from_secs(1));
+    let ids = [a.id(), b.id(), c.id(), past.id()];
+    assert_eq!(ids.iter().collect::<HashSet<_>>().len(), 4);
+
+    // Stable across resets, including the one registering an elapsed delay.
+    a.reset(Duration::from_secs(1));
+    past.reset(Duration::from_secs(1));
+    assert_eq!(a.id(), ids[0]);
+    assert_eq!(past.id(), ids[3]);
+}
@@ -0,0 +1,24 @@
+use std::time::{Duration, Instant};
+
+use futures_timer::{Delay, GlobalTimerBuilder};
+
+#[async_std::test]
+async fn spin_threshold() {
+    GlobalTimerBuilder::new()
+        .spin_threshold(Duration::from_millis(2))
+        .build_global()
+        .unwrap();
+
+    // Timing is best-effort on a loaded machine, so only check that short
+    // delays don't overshoot wildly on average.
+    let dur = Duration::from_millis(1);
+    let mut total = Duration::from_secs(0);
+    for _ in 0..20 {
+        let i = Instant::now();
+        Delay::new(dur).await;
+        let elapsed = i.elapsed();
+        assert!(elapsed >= dur);
+        total += elapsed - dur;
+    }
+    assert!(total / 20 < Duration::from_millis(50));
+}
@@ -0,0 +1,58 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use futures::poll;
+use futures_timer::{Delay, SystemDelay};
+
+/// A system clock which only moves when told to.
+fn mock_clock() -> (Arc<Mutex<SystemTime>>, impl Fn() -> SystemTime + Unpin) {
+    let now = Arc::new(Mutex::new(
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000),
+    ));
+    let clock = {
+        let now = now.clone();
+        move || *now.lock().unwrap()
+    };
+    (now, clock)
+}
+
+#[async_std::test]
+async fn clock_jumps_forward() {
+    let (now, clock) = mock_clock();
+    let target = clock() + Duration::from_secs(3600);
+    let mut d = SystemDelay::with_clock(target, clock);
+    d.set_check_interval(Duration::from_millis(10));
+    assert!(poll!(&mut d).is_pending());
+
+    *now.lock().unwrap() += Duration::from_secs(3600);
+    let i = Instant::now();
+    d.await;
+    assert!(i.elapsed() < Duration::from_secs(1));
+}
+
+#[async_std::test]
+async fn clock_jumps_backward() {
+    let (now, clock) = mock_clock();
+    let target = clock() + Duration::from_millis(20);
+    let mut d = SystemDelay::with_clock(target, clock);
+
+    // The monotonic delay elapses, but the wall clock has been stepped back
+    // and so hasn't reached the target.
+    *now.lock().unwrap() -= Duration::from_secs(60);
+    Delay::new(Duration::from_millis(50)).await;
+    assert!(poll!(&mut d).is_pending());
+
+    *now.lock().unwrap() += Duration::from_secs(61);
+    d.set_check_interval(Duration::from_millis(10));
+    let i = Instant::now();
+    d.await;
+    assert!(i.elapsed() < Duration::from_secs(2));
+}
+
+#[async_std::test]
+async fn real_clock() {
+    let i = Instant::now();
+    let dur = Duration::from_millis(50);
+    SystemDelay::new(SystemTime::now() + dur).await;
+    assert!(i.elapsed() >= dur - Duration::from_millis(5));
+}
@@ -1,7 +1,12 @@
 use std::error::Error;
+use std::io;
 use std::time::{Duration, Instant};
 
-use futures_timer::Delay;
+use futures::{future, FutureExt};
+use futures_timer::{
+    elapsed_error, timeout, timeout_and_cancel, timeout_or_else, timeout_with, with_deadline,
+    Delay, TimeoutGuard,
+};
 
 #[async_std::test]
 async fn smoke() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
@@ -19,3 +24,110 @@ async fn two() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
     Delay::new(dur).await;
     Ok(())
 }
+
+#[async_std::test]
+async fn completes_in_time() {
+    let res = timeout(Duration::from_secs(10), async { 5 }).await;
+    assert_eq!(res, Ok(5));
+}
+
+#[async_std::test]
+async fn elapses() {
+    let res = timeout(Duration::from_millis(10), future::pending::<()>()).await;
+    assert!(res.is_err());
+}
+
+#[test]
+fn elapsed_error_kind() {
+    assert_eq!(elapsed_error().kind(), io::ErrorKind::TimedOut);
+}
+
+#[async_std::test]
+async fn into_inner() {
+    let mut t = timeout(Duration::from_secs(3600), future::ready(5));
+    assert_eq!(t.get_ref().clone().await, 5);
+    *t.get_mut() = future::ready(6);
+    assert_eq!(t.into_inner().await, 6);
+}
+
+#[async_std::test]
+async fn cancel_drops_future() {
+    use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+    use std::sync::Arc;
+
+    struct SetOnDrop(Arc<AtomicBool>);
+
+    impl Drop for SetOnDrop {
+        fn drop(&mut self) {
+            self.0.store(true, SeqCst);
+        }
+    }
+
+    let dropped = Arc::new(AtomicBool::new(false));
+    let guard = SetOnDrop(dropped.clone());
+    let inner = async move {
+        let _guard = guard;
+        future::pending::<()>().await
+    };
+    let mut t = Box::pin(timeout_and_cancel(Duration::from_millis(10), inner));
+    assert!(t.as_mut().await.is_err());
+    assert!(dropped.load(SeqCst));
+}
+
+#[async_std::test]
+async fn with_deadline_ready_wins() {
+    let past = Instant::now() - Duration::from_secs(1);
+    assert_eq!(with_deadline(past, future::ready(1)).await, Ok(1));
+    let err = with_deadline(past, future::pending::<()>()).await;
+    assert!(err.is_err());
+}
+
+#[test]
+fn debug_without_debug_future() {
+    struct NotDebug;
+
+    let t = timeout(Duration::from_secs(1), async { NotDebug });
+    assert!(format!("{:?}", t).starts_with("Timeout"));
+}
+
+#[async_std::test]
+async fn or_else_fallback() {
+    let dur = Duration::from_millis(10);
+    let n = timeout_or_else(dur, future::pending::<u32>(), || 7).await;
+    assert_eq!(n, 7);
+
+    let n = timeout_or_else(dur, future::ready(1), || unreachable!()).await;
+    assert_eq!(n, 1);
+}
+
+#[async_std::test]
+async fn guard_activity_keeps_it_alive() {
+    let idle = Duration::from_millis(50);
+    let mut guard = TimeoutGuard::new(idle);
+    let start = Instant::now();
+    for _ in 0..4 {
+        Delay::new(idle / 2).await;
+        assert!(future::poll_fn(|cx| guard.poll_expired(cx))
+            .now_or_never()
+            .is_none());
+        guard.reset_on_activity();
+    }
+    assert!(start.elapsed() >= idle * 2);
+
+    future::poll_fn(|cx| guard.poll_expired(cx)).await;
+    assert!(start.elapsed() >= idle * 3);
+}
+
+#[async_std::test]
+async fn with_shared_deadline() {
+    let at = Instant::now() + Duration::from_millis(20);
+    let deadline = Delay::new_at(at);
+    let a = timeout_with(deadline.clone(), future::pending::<()>());
+    let b = timeout_with(deadline, future::pending::<()>());
+    let (a, b) = future::join(a, b).await;
+    assert!(a.is_err() && b.is_err());
+    assert!(Instant::now() >= at);
+
+    let ok = timeout_with(Delay::new_at(at), future::ready(3)).await;
+    assert_eq!(ok, Ok(3));
+}
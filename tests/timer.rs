@@ -0,0 +1,480 @@
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::sync::{Arc, Mutex};
+use std::task::Context;
+use std::time::{Duration, Instant};
+
+use futures::task::{noop_waker, waker, ArcWake};
+use futures::{poll, FutureExt};
+use futures_timer::{Delay, Timer, TimerHandle};
+
+/// Counts how many times it's been woken.
+struct Count(AtomicUsize);
+
+impl ArcWake for Count {
+    fn wake_by_ref(me: &Arc<Self>) {
+        me.0.fetch_add(1, SeqCst);
+    }
+}
+
+/// Records its id in a shared list when woken.
+struct Record(usize, Arc<Mutex<Vec<usize>>>);
+
+impl ArcWake for Record {
+    fn wake_by_ref(me: &Arc<Self>) {
+        me.1.lock().unwrap().push(me.0);
+    }
+}
+
+/// Processes any pending timer updates without firing anything.
+fn process(timer: &mut Timer) {
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    assert!(Pin::new(timer).poll(&mut cx).is_pending());
+}
+
+#[async_std::test]
+async fn far_future_on_local_handle() {
+    let timer = Timer::new();
+    let at = Instant::now() + Duration::from_secs(3600);
+    let mut d = Delay::new_at_handle(at, timer.handle());
+    assert!(poll!(&mut d).is_pending());
+    assert_eq!(timer.len(), 1);
+}
+
+#[test]
+fn coalesce_window() {
+    let mut timer = Timer::new();
+    timer.set_coalesce_window(Duration::from_millis(5));
+    let now = Instant::now();
+    let mut a = Delay::new_at_handle(now + Duration::from_millis(10), timer.handle());
+    let mut b = Delay::new_at_handle(now + Duration::from_millis(12), timer.handle());
+    process(&mut timer);
+
+    timer.advance_to(now + Duration::from_millis(10));
+    assert!((&mut a).now_or_never().is_some());
+    assert!((&mut b).now_or_never().is_some());
+}
+
+#[test]
+fn no_coalesce_window() {
+    let mut timer = Timer::new();
+    let now = Instant::now();
+    let mut a = Delay::new_at_handle(now + Duration::from_millis(10), timer.handle());
+    let mut b = Delay::new_at_handle(now + Duration::from_millis(12), timer.handle());
+    process(&mut timer);
+
+    timer.advance_to(now + Duration::from_millis(10));
+    assert!((&mut a).now_or_never().is_some());
+    assert!((&mut b).now_or_never().is_none());
+}
+
+#[test]
+fn granularity_rounds_up() {
+    let g = Duration::from_millis(15);
+    let mut timer = Timer::new();
+    timer.set_granularity(g);
+    let at = Instant::now() + Duration::from_secs(1);
+    let mut d = Delay::new_at_handle(at, timer.handle());
+    process(&mut timer);
+    let tick = timer.next_event().unwrap();
+    assert!(tick >= at && tick - at < g);
+
+    // Already on a tick, so nothing to round.
+    d.reset_at(tick);
+    process(&mut timer);
+    assert_eq!(timer.next_event(), Some(tick));
+
+    d.reset_at(tick + Duration::from_millis(1));
+    process(&mut timer);
+    assert_eq!(timer.next_event(), Some(tick + g));
+}
+
+#[test]
+fn granularity_too_large_to_round() {
+    let mut timer = Timer::new();
+    timer.set_granularity(Duration::from_secs(u64::MAX));
+    let at = Instant::now() + Duration::from_secs(1);
+    let _d = Delay::new_at_handle(at, timer.handle());
+    process(&mut timer);
+    assert_eq!(timer.next_event(), Some(at));
+}
+
+#[test]
+fn is_alive() {
+    let timer = Timer::new();
+    let handle = timer.handle();
+    assert!(handle.is_alive());
+    drop(timer);
+    assert!(!handle.is_alive());
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn on_wakeup() {
+    let mut timer = Timer::new();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let fired = Arc::new(AtomicUsize::new(0));
+    let (calls2, fired2) = (calls.clone(), fired.clone());
+    timer.on_wakeup(move |stats| {
+        calls2.fetch_add(1, SeqCst);
+        fired2.fetch_add(stats.fired, SeqCst);
+        assert!(stats.actual_park.is_none());
+    });
+
+    let at = Instant::now() + Duration::from_secs(1);
+    let _a = Delay::new_at_handle(at, timer.handle());
+    let _b = Delay::new_at_handle(at, timer.handle());
+    process(&mut timer);
+    timer.advance_to(at);
+    timer.advance_to(at);
+    assert_eq!(calls.load(SeqCst), 2);
+    assert_eq!(fired.load(SeqCst), 2);
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn lateness_histogram() {
+    let mut timer = Timer::new();
+    assert_eq!(timer.lateness_histogram().quantile(0.5), None);
+
+    let at = Instant::now() + Duration::from_secs(1);
+    let _delays = (0..20)
+        .map(|i| Delay::new_at_handle(at + Duration::from_micros(i), timer.handle()))
+        .collect::<Vec<_>>();
+    process(&mut timer);
+    let late = Duration::from_millis(5);
+    assert_eq!(timer.advance_to(at + late), 20);
+
+    let h = timer.lateness_histogram();
+    assert_eq!(h.count(), 20);
+    assert_eq!(h.max(), late);
+    let p50 = h.quantile(0.5).unwrap();
+    assert!(p50 >= late / 2 && p50 <= late);
+}
+
+#[test]
+fn elapsed_not_registered() {
+    let mut timer = Timer::new();
+    let mut d = Delay::new_at_handle(Instant::now(), timer.handle());
+    assert_eq!(timer.len(), 0);
+    assert!((&mut d).now_or_never().is_some());
+
+    d.reset(Duration::from_secs(3600));
+    process(&mut timer);
+    assert_eq!(timer.len(), 1);
+    assert!((&mut d).now_or_never().is_none());
+}
+
+#[async_std::test]
+async fn global_handle() {
+    let dur = Duration::from_millis(10);
+    let a = Delay::new_handle(dur, TimerHandle::global());
+    let b = Delay::new_handle(dur, TimerHandle::global());
+    futures::join!(a, b);
+    assert!(TimerHandle::global().is_alive());
+}
+
+#[test]
+fn equal_deadlines_fire_in_order() {
+    let mut timer = Timer::new();
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let at = Instant::now() + Duration::from_secs(1);
+    let mut delays = (0..5)
+        .map(|_| Delay::new_at_handle(at, timer.handle()))
+        .collect::<Vec<_>>();
+    process(&mut timer);
+    for (i, d) in delays.iter_mut().enumerate() {
+        let waker = waker(Arc::new(Record(i, order.clone())));
+        let mut cx = Context::from_waker(&waker);
+        assert!(Pin::new(d).poll(&mut cx).is_pending());
+    }
+
+    timer.advance_to(at);
+    assert_eq!(*order.lock().unwrap(), [0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn drive_by_hand() {
+    let mut timer = Timer::new();
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    assert_eq!(timer.next_event(), None);
+
+    let at = Instant::now() + Duration::from_millis(20);
+    let mut d = Delay::new_at_handle(at, timer.handle());
+    assert!(Pin::new(&mut d).poll(&mut cx).is_pending());
+
+    // The new delay isn't visible until the timer has been polled.
+    assert_eq!(timer.next_event(), None);
+    loop {
+        assert!(Pin::new(&mut timer).poll(&mut cx).is_pending());
+        timer.advance();
+        if Pin::new(&mut d).poll(&mut cx).is_ready() {
+            break;
+        }
+        let next = timer.next_event().unwrap();
+        assert_eq!(next, at);
+        std::thread::sleep(next.saturating_duration_since(Instant::now()));
+    }
+    assert!(Instant::now() >= at);
+    assert_eq!(timer.next_event(), None);
+}
+
+#[test]
+fn fire_immediately_inert() {
+    let timer = Timer::new();
+    let at = Instant::now() + Duration::from_secs(3600);
+    let mut invalidated = Delay::new_at_handle(at, timer.handle());
+    let handle = timer.handle();
+    drop(timer);
+    let mut inert = Delay::new_at_handle(at, handle);
+
+    invalidated.fire_immediately();
+    inert.fire_immediately();
+    assert!((&mut invalidated).now_or_never().is_some());
+    assert!((&mut inert).now_or_never().is_some());
+}
+
+#[test]
+fn default() {
+    let timer = Timer::default();
+    assert!(timer.handle().is_alive());
+    assert!(timer.is_empty());
+}
+
+#[test]
+fn reset_keeps_waker() {
+    let mut timer = Timer::new();
+    let count = Arc::new(Count(AtomicUsize::new(0)));
+    let waker = waker(count.clone());
+    let mut cx = Context::from_waker(&waker);
+    let now = Instant::now();
+    let mut d = Delay::new_at_handle(now + Duration::from_secs(3600), timer.handle());
+    assert!(Pin::new(&mut d).poll(&mut cx).is_pending());
+
+    let at = now + Duration::from_secs(1);
+    d.reset_at(at);
+    process(&mut timer);
+    timer.advance_to(at);
+    assert_eq!(count.0.load(SeqCst), 1);
+}
+
+#[test]
+fn updates_coalesce_wakeups() {
+    let mut timer = Timer::new();
+    let count = Arc::new(Count(AtomicUsize::new(0)));
+    let waker = waker(count.clone());
+    let mut cx = Context::from_waker(&waker);
+    assert!(Pin::new(&mut timer).poll(&mut cx).is_pending());
+
+    // The first update takes the timer's waker, so the rest don't wake it
+    // again until it's been polled.
+    let at = Instant::now() + Duration::from_secs(3600);
+    let mut delays = (0..10_000)
+        .map(|_| Delay::new_at_handle(at, timer.handle()))
+        .collect::<Vec<_>>();
+    for d in delays.iter_mut() {
+        d.reset_at(at + Duration::from_secs(1));
+    }
+    assert_eq!(count.0.load(SeqCst), 1);
+
+    assert!(Pin::new(&mut timer).poll(&mut cx).is_pending());
+    drop(delays);
+    assert_eq!(count.0.load(SeqCst), 2);
+}
+
+#[test]
+fn clock_jump_fires_everything_due() {
+    let mut timer = Timer::new();
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let now = Instant::now();
+    let mut delays = [1, 60, 3600, 7200]
+        .iter()
+        .map(|&s| Delay::new_at_handle(now + Duration::from_secs(s), timer.handle()))
+        .collect::<Vec<_>>();
+    process(&mut timer);
+
+    // Simulate the clock jumping forward by an hour, as after a suspend.
+    timer.advance_to(now + Duration::from_secs(3600));
+    let fired = delays
+        .iter_mut()
+        .map(|d| Pin::new(d).poll(&mut cx).is_ready())
+        .collect::<Vec<_>>();
+    assert_eq!(fired, [true, true, true, false]);
+    assert_eq!(timer.next_event(), Some(now + Duration::from_secs(7200)));
+}
+
+#[test]
+fn advance_fired_count() {
+    let mut timer = Timer::new();
+    let now = Instant::now();
+    let _delays = [1, 1, 2]
+        .iter()
+        .map(|&s| Delay::new_at_handle(now + Duration::from_secs(s), timer.handle()))
+        .collect::<Vec<_>>();
+    process(&mut timer);
+    assert_eq!(timer.advance(), 0);
+    assert_eq!(timer.advance_to(now + Duration::from_secs(1)), 2);
+    assert_eq!(timer.advance_to(now + Duration::from_secs(1)), 0);
+    assert_eq!(timer.advance_to(now + Duration::from_secs(2)), 1);
+}
+
+#[test]
+fn reset_race_never_fires_early() {
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+
+    let mut timer = Timer::new();
+    let handle = timer.handle();
+    let done = Arc::new(AtomicBool::new(false));
+    let done2 = done.clone();
+    let pump = thread::spawn(move || {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        while !done2.load(SeqCst) {
+            assert!(Pin::new(&mut timer).poll(&mut cx).is_pending());
+            timer.advance();
+        }
+    });
+
+    // Each reset to the past is immediately superseded by one to the far
+    // future. The first may legitimately fire, but once the second has been
+    // published the delay must stay pending whatever order the timer sees the
+    // updates in.
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let far = Instant::now() + Duration::from_secs(3600);
+    let mut d = Delay::new_at_handle(far, handle);
+    for _ in 0..500_000 {
+        d.reset_at(Instant::now());
+        d.reset_at(far);
+        assert!(Pin::new(&mut d).poll(&mut cx).is_pending());
+    }
+
+    done.store(true, SeqCst);
+    pump.join().unwrap();
+}
+
+#[test]
+fn fire_all() {
+    let mut timer = Timer::new();
+    let far = Instant::now() + Duration::from_secs(3600);
+    let mut delays = (0..3)
+        .map(|i| Delay::new_at_handle(far + Duration::from_secs(i), timer.handle()))
+        .collect::<Vec<_>>();
+    process(&mut timer);
+    // Not yet seen by the timer, but still fired.
+    delays.push(Delay::new_at_handle(far, timer.handle()));
+
+    assert_eq!(timer.fire_all(), 4);
+    assert_eq!(timer.next_event(), None);
+    for d in delays {
+        assert_eq!(d.now_or_never(), Some(()));
+    }
+}
+
+#[test]
+fn deadline_tracks_latest_schedule() {
+    let mut timer = Timer::new();
+    let now = Instant::now();
+    let at = now + Duration::from_secs(10);
+    let mut d = Delay::new_at_handle(at, timer.handle());
+    assert_eq!(d.deadline(), at);
+
+    d.reset_at(at + Duration::from_secs(1));
+    assert_eq!(d.deadline(), at + Duration::from_secs(1));
+    d.extend(Duration::from_secs(1));
+    assert_eq!(d.deadline(), at + Duration::from_secs(2));
+
+    // Fired delays keep their deadline.
+    process(&mut timer);
+    timer.advance_to(at + Duration::from_secs(2));
+    assert!((&mut d).now_or_never().is_some());
+    assert_eq!(d.deadline(), at + Duration::from_secs(2));
+
+    // Delays which elapsed before they were registered.
+    let past = now - Duration::from_secs(1);
+    let mut e = Delay::new_at_handle(past, timer.handle());
+    assert_eq!(e.deadline(), past);
+    e.reset_at(at);
+    assert_eq!(e.deadline(), at);
+
+    // And inert delays whose timer has gone away.
+    let handle = timer.handle();
+    drop(timer);
+    d.reset_at(at + Duration::from_secs(3));
+    assert_eq!(d.deadline(), at + Duration::from_secs(3));
+    let f = Delay::new_at_handle(at, handle);
+    assert_eq!(f.deadline(), at);
+}
+
+#[test]
+fn batch_fires_in_deadline_order() {
+    let mut timer = Timer::new();
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let at = Instant::now() + Duration::from_secs(1);
+    // Scheduled latest-first, so only the deadlines put them in order.
+    let mut delays = (0..5)
+        .rev()
+        .map(|i| {
+            (
+                i,
+                Delay::new_at_handle(at + Duration::from_millis(i as u64), timer.handle()),
+            )
+        })
+        .collect::<Vec<_>>();
+    process(&mut timer);
+    for (i, d) in delays.iter_mut() {
+        let waker = waker(Arc::new(Record(*i, order.clone())));
+        let mut cx = Context::from_waker(&waker);
+        assert!(Pin::new(d).poll(&mut cx).is_pending());
+    }
+
+    assert_eq!(timer.advance_to(at + Duration::from_secs(1)), 5);
+    assert_eq!(*order.lock().unwrap(), [0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn into_shutdown_waits_for_delays() {
+    let timer = Timer::new();
+    let count = Arc::new(Count(AtomicUsize::new(0)));
+    let waker = waker(count.clone());
+    let mut cx = Context::from_waker(&waker);
+    let at = Instant::now() + Duration::from_secs(3600);
+    let mut delays = (0..3)
+        .map(|_| Delay::new_at_handle(at, timer.handle()))
+        .collect::<Vec<_>>();
+    for d in delays.iter_mut() {
+        assert!(Pin::new(d).poll(&mut cx).is_pending());
+    }
+    let handle = timer.handle();
+
+    // Every blocked task is woken as soon as shutdown starts.
+    let mut guard = timer.into_shutdown();
+    assert_eq!(count.0.load(SeqCst), 3);
+    assert!(Pin::new(&mut guard).poll(&mut cx).is_pending());
+
+    // And find their timer gone when they next poll, without that letting
+    // the guard resolve while they're still alive.
+    for d in delays.iter_mut() {
+        let res = panic::catch_unwind(AssertUnwindSafe(|| Pin::new(d).poll(&mut cx)));
+        let err = res.expect_err("polling a delay after shutdown should panic");
+        assert_eq!(err.downcast_ref::<&str>(), Some(&"timer has gone away"));
+    }
+    assert!(Pin::new(&mut guard).poll(&mut cx).is_pending());
+
+    // Delays created after that are inert and don't hold it up.
+    let mut late = Delay::new_at_handle(at, handle);
+    late.reset_at(at);
+    drop(delays.pop());
+    delays[0].reset_at(at);
+    assert!(Pin::new(&mut guard).poll(&mut cx).is_pending());
+
+    drop(delays);
+    assert!(Pin::new(&mut guard).poll(&mut cx).is_ready());
+    assert!(count.0.load(SeqCst) > 3);
+}
@@ -0,0 +1,61 @@
+#![cfg(feature = "tracing")]
+
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use futures_timer::Delay;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+static MESSAGES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Records the message of every event.
+struct Recorder;
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        struct Message;
+
+        impl Visit for Message {
+            fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                if field.name() == "message" {
+                    MESSAGES.lock().unwrap().push(format!("{:?}", value));
+                }
+            }
+        }
+
+        event.record(&mut Message);
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[async_std::test]
+async fn fired_event() {
+    tracing::subscriber::set_global_default(Recorder).unwrap();
+
+    let mut d = Delay::new(Duration::from_millis(10));
+    d.reset(Duration::from_millis(20));
+    d.await;
+
+    let messages = MESSAGES.lock().unwrap();
+    for expected in ["delay created", "delay reset", "delay fired"].iter() {
+        assert!(messages.iter().any(|m| m == expected), "{:?}", messages);
+    }
+}
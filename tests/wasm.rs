@@ -0,0 +1,40 @@
+#![cfg(all(target_arch = "wasm32", feature = "wasm-bindgen"))]
+
+use std::time::Duration;
+
+use futures_timer::Delay;
+use instant::Instant;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen_test]
+async fn new_at() {
+    let i = Instant::now();
+    let dur = Duration::from_millis(10);
+    Delay::new_at(i + dur).await;
+    assert!(i.elapsed() >= dur);
+}
+
+#[wasm_bindgen_test]
+async fn new_at_past() {
+    let at = Instant::now();
+    Delay::new_at(at).await;
+}
+
+#[wasm_bindgen_test]
+async fn sub_millisecond_beats_timeout() {
+    use futures::future::{select, Either};
+
+    let zero = Delay::new(Duration::from_micros(200));
+    let five = Delay::new(Duration::from_millis(5));
+    match select(zero, five).await {
+        Either::Left(_) => {}
+        Either::Right(_) => panic!("5ms delay fired before the sub-millisecond one"),
+    }
+}
+
+#[wasm_bindgen_test]
+fn auto_traits() {
+    // `Delay` is only `Send` through a wrapper which panics if it's used off
+    // the thread which created it.
+    static_assertions::assert_impl_all!(Delay: Send, Sync, Unpin);
+}